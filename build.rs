@@ -0,0 +1,47 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Writes the list of files compiled into the binary. It is empty unless the `embed-assets`
+/// feature is enabled, in which case it holds every file in the assets directory.
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let out_path = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("embedded_assets.rs");
+    let mut files = Vec::new();
+
+    if env::var_os("CARGO_FEATURE_EMBED_ASSETS").is_some() {
+        let root = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap()).join("assets");
+        println!("cargo:rerun-if-changed={}", root.display());
+        collect_files(&root, &mut files);
+        files.sort();
+
+        let entries = files
+            .iter()
+            .map(|path| {
+                let relative = path.strip_prefix(&root).unwrap();
+                let name = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                format!("    ({:?}, include_bytes!({:?})),\n", name, path)
+            })
+            .collect::<String>();
+        fs::write(&out_path, format!("&[\n{}]\n", entries)).unwrap();
+    } else {
+        fs::write(&out_path, "&[]\n").unwrap();
+    }
+}
+
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(directory).unwrap().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Reads spritesheets exported from Aseprite with File > Export Sprite Sheet and JSON data
+//! enabled, in either the hash or the array layout.
+//!
+//! - Every frame keeps its own rectangle in the image, so frames don't need to be on a grid, and
+//!   trimmed frames are drawn at their original position.
+//! - Frame tags become clips named after the tag. Files without tags have a single `default` clip
+//!   with every frame.
+//! - Tag directions map to playback modes: `forward` and `reverse` loop, `pingpong` and
+//!   `pingpong_reverse` ping-pong. A tag with a repeat count of 1 plays once.
+//! - Slices, e.g. a `hitbox` or a slice with a pivot, are kept per frame.
+
+use std::{collections::HashMap, fmt, time::Duration};
+
+use serde::{
+    de::{MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+use super::{AnimationClip, Frame, PlaybackMode, SliceKey};
+use crate::spritesheet::SpriteRegion;
+
+#[derive(Deserialize)]
+pub(super) struct AsepriteFile {
+    frames: FrameList,
+    meta: Meta,
+}
+
+/// Frames in file order. The hash layout stores them in an object keyed by file name, whose order
+/// is the frame order, so it is read as a list instead of a map.
+struct FrameList(Vec<AsepriteFrame>);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AsepriteFrame {
+    frame: Rect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    sprite_source_size: Option<Rect>,
+    duration: u64,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Meta {
+    image: String,
+    #[serde(default)]
+    frame_tags: Vec<Tag>,
+    #[serde(default)]
+    slices: Vec<Slice>,
+}
+
+#[derive(Deserialize)]
+struct Tag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+    repeat: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Slice {
+    name: String,
+    keys: Vec<Key>,
+}
+
+#[derive(Deserialize)]
+struct Key {
+    frame: usize,
+    bounds: Rect,
+    pivot: Option<Point>,
+}
+
+#[derive(Deserialize)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+type Clips = HashMap<String, AnimationClip>;
+type Slices = HashMap<String, Vec<SliceKey>>;
+
+impl AsepriteFile {
+    /// Path of the image, relative to the JSON file
+    pub fn image(&self) -> &str {
+        &self.meta.image
+    }
+
+    pub fn animations(&self) -> Result<(Clips, Slices), String> {
+        let frames = self
+            .frames
+            .0
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| frame.to_frame(index))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut clips = HashMap::new();
+        if self.meta.frame_tags.is_empty() {
+            clips.insert(
+                "default".to_string(),
+                AnimationClip {
+                    frames: frames.clone(),
+                    mode: PlaybackMode::Loop,
+                },
+            );
+        }
+
+        for tag in &self.meta.frame_tags {
+            let Some(tag_frames) = frames.get(tag.from..=tag.to) else {
+                return Err(format!(
+                    "tag {} uses frames {}-{} of {}",
+                    tag.name,
+                    tag.from,
+                    tag.to,
+                    frames.len()
+                ));
+            };
+
+            let mut tag_frames = tag_frames.to_vec();
+            if tag.direction.ends_with("reverse") {
+                tag_frames.reverse();
+            }
+            let mode = match (tag.direction.starts_with("pingpong"), tag.repeat.as_deref()) {
+                (_, Some("1")) => PlaybackMode::Once,
+                (true, _) => PlaybackMode::PingPong,
+                (false, _) => PlaybackMode::Loop,
+            };
+
+            clips.insert(
+                tag.name.clone(),
+                AnimationClip {
+                    frames: tag_frames,
+                    mode,
+                },
+            );
+        }
+
+        let slices = self
+            .meta
+            .slices
+            .iter()
+            .map(|slice| {
+                let keys = slice
+                    .keys
+                    .iter()
+                    .map(|key| SliceKey {
+                        frame: key.frame,
+                        bounds: key.bounds.into(),
+                        pivot: key.pivot.as_ref().map(|pivot| (pivot.x, pivot.y)),
+                    })
+                    .collect();
+                (slice.name.clone(), keys)
+            })
+            .collect();
+
+        Ok((clips, slices))
+    }
+}
+
+impl AsepriteFrame {
+    fn to_frame(&self, index: usize) -> Result<Frame, String> {
+        if self.rotated {
+            return Err(format!(
+                "frame {} is rotated, which is not supported",
+                index
+            ));
+        }
+
+        let offset = match (self.trimmed, self.sprite_source_size) {
+            (true, Some(source)) => (source.x as i32, source.y as i32),
+            _ => (0, 0),
+        };
+
+        Ok(Frame {
+            index,
+            region: self.frame.into(),
+            offset,
+            flip_x: false,
+            flip_y: false,
+            duration: Duration::from_millis(self.duration),
+        })
+    }
+}
+
+impl From<Rect> for SpriteRegion {
+    fn from(rect: Rect) -> Self {
+        SpriteRegion {
+            x: rect.x,
+            y: rect.y,
+            width: rect.w,
+            height: rect.h,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for FrameList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FrameListVisitor;
+
+        impl<'de> Visitor<'de> for FrameListVisitor {
+            type Value = FrameList;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an array or object of frames")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<FrameList, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(frame) = seq.next_element()? {
+                    frames.push(frame);
+                }
+                Ok(FrameList(frames))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<FrameList, A::Error> {
+                let mut frames = Vec::new();
+                while let Some((_, frame)) = map.next_entry::<String, _>()? {
+                    frames.push(frame);
+                }
+                Ok(FrameList(frames))
+            }
+        }
+
+        deserializer.deserialize_any(FrameListVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"{
+        "frames": {
+            "hero 0.aseprite": {
+                "frame": { "x": 0, "y": 0, "w": 12, "h": 16 }, "rotated": false, "trimmed": true,
+                "spriteSourceSize": { "x": 2, "y": 0, "w": 12, "h": 16 },
+                "sourceSize": { "w": 16, "h": 16 }, "duration": 100
+            },
+            "hero 1.aseprite": {
+                "frame": { "x": 12, "y": 0, "w": 16, "h": 16 }, "rotated": false, "trimmed": false,
+                "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+                "sourceSize": { "w": 16, "h": 16 }, "duration": 150
+            }
+        },
+        "meta": {
+            "image": "hero.png",
+            "frameTags": [
+                { "name": "walk", "from": 0, "to": 1, "direction": "pingpong" },
+                { "name": "hit", "from": 1, "to": 1, "direction": "forward", "repeat": "1" }
+            ],
+            "slices": [
+                { "name": "hitbox", "keys": [{ "frame": 0, "bounds": { "x": 2, "y": 8, "w": 12, "h": 8 }, "pivot": { "x": 8, "y": 16 } }] }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn reads_frames_tags_and_slices_from_hash_export() {
+        let file: AsepriteFile = serde_json::from_str(EXPORT).unwrap();
+        let (clips, slices) = file.animations().unwrap();
+
+        let walk = &clips["walk"];
+        assert_eq!(walk.mode, PlaybackMode::PingPong);
+        assert_eq!(walk.frames[0].offset, (2, 0));
+        assert_eq!(walk.frames[1].region.x, 12);
+        assert_eq!(walk.frames[1].duration, Duration::from_millis(150));
+        assert_eq!(clips["hit"].mode, PlaybackMode::Once);
+        assert_eq!(slices["hitbox"][0].pivot, Some((8, 16)));
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::{
+    assets::{AssetError, Assets},
+    components::AnimationController,
+    movement_util::Direction,
+};
+
+/// What the state machine's transitions can check, updated by the systems that own the state
+#[derive(Debug, Clone, Default)]
+pub struct AnimationParams {
+    pub moving: bool,
+    pub running: bool,
+    pub surfing: bool,
+    pub direction: Direction,
+    /// Set to start casting, cleared when the next state is entered or no transition takes it
+    pub casting: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AnimationState {
+    /// Clip to play, where `{direction}` is replaced by the direction the sprite faces
+    pub clip: String,
+    /// Frames that play before the state can be left. The state is only left after a whole number
+    /// of these, so a walk cycle always finishes its step.
+    #[serde(default)]
+    pub min_frames: usize,
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+}
+
+impl AnimationState {
+    pub fn clip_name(&self, direction: Direction) -> String {
+        self.clip.replace("{direction}", direction.name())
+    }
+
+    fn can_leave(&self, frames_played: usize) -> bool {
+        self.min_frames == 0
+            || (frames_played >= self.min_frames && frames_played.is_multiple_of(self.min_frames))
+    }
+}
+
+fn default_speed() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Param {
+    Moving,
+    Running,
+    Surfing,
+    Casting,
+    Facing(Direction),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Condition {
+    param: Param,
+    expected: bool,
+}
+
+impl Condition {
+    /// Parses a condition like `moving`, `!running` or `facing_up`
+    fn parse(text: &str) -> Option<Self> {
+        let (expected, name) = match text.strip_prefix('!') {
+            Some(name) => (false, name),
+            None => (true, text),
+        };
+        let param = match name {
+            "moving" => Param::Moving,
+            "running" => Param::Running,
+            "surfing" => Param::Surfing,
+            "casting" => Param::Casting,
+            "facing_up" => Param::Facing(Direction::Up),
+            "facing_down" => Param::Facing(Direction::Down),
+            "facing_left" => Param::Facing(Direction::Left),
+            "facing_right" => Param::Facing(Direction::Right),
+            _ => return None,
+        };
+        Some(Self { param, expected })
+    }
+
+    /// Triggers are set for one transition rather than describing the character's current state
+    fn is_trigger(&self) -> bool {
+        self.param == Param::Casting && self.expected
+    }
+
+    fn holds(&self, params: &AnimationParams) -> bool {
+        let value = match self.param {
+            Param::Moving => params.moving,
+            Param::Running => params.running,
+            Param::Surfing => params.surfing,
+            Param::Casting => params.casting,
+            Param::Facing(direction) => params.direction == direction,
+        };
+        value == self.expected
+    }
+}
+
+#[derive(Debug)]
+struct Transition {
+    /// States the transition leaves, any state when empty
+    from: Vec<String>,
+    to: String,
+    when: Vec<Condition>,
+}
+
+/// States of an animated character and when to move between them, loaded from a JSON file like:
+///
+/// ```json
+/// {
+///   "initial": "idle",
+///   "states": {
+///     "idle": { "clip": "idle_{direction}" },
+///     "walk": { "clip": "walk_{direction}", "min_frames": 2 }
+///   },
+///   "transitions": [
+///     { "to": "walk", "when": ["moving"] },
+///     { "from": ["walk"], "to": "idle", "when": ["!moving"] }
+///   ],
+///   "events": { "walk_down": { "1": ["footstep"] } }
+/// }
+/// ```
+///
+/// Transitions are checked in order and the first one whose conditions all hold is taken. `casting`
+/// is a trigger, cleared whenever a state is entered or no transition takes it. Events are sent
+/// when a frame of a clip starts showing.
+#[derive(Debug)]
+pub struct StateMachine {
+    initial: String,
+    states: HashMap<String, AnimationState>,
+    transitions: Vec<Transition>,
+    events: HashMap<String, HashMap<usize, Vec<String>>>,
+}
+
+#[derive(Deserialize)]
+struct StateMachineFile {
+    initial: String,
+    states: HashMap<String, AnimationState>,
+    #[serde(default)]
+    transitions: Vec<TransitionEntry>,
+    #[serde(default)]
+    events: HashMap<String, HashMap<usize, Vec<String>>>,
+}
+
+#[derive(Deserialize)]
+struct TransitionEntry {
+    #[serde(default)]
+    from: Vec<String>,
+    to: String,
+    #[serde(default)]
+    when: Vec<String>,
+}
+
+impl StateMachine {
+    pub fn load(assets: &Assets, path: &str) -> Result<Self, AssetError> {
+        let bytes = assets.read(path)?;
+        let file = serde_json::from_slice(&bytes).map_err(|source| AssetError::Json {
+            path: assets.source().display_path(path),
+            source,
+        })?;
+        Self::from_file(file).map_err(|reason| AssetError::Invalid {
+            path: assets.source().display_path(path),
+            reason,
+        })
+    }
+
+    fn from_file(file: StateMachineFile) -> Result<Self, String> {
+        let check_state = |name: &str| match file.states.contains_key(name) {
+            true => Ok(()),
+            false => Err(format!("there is no state {}", name)),
+        };
+
+        check_state(&file.initial)?;
+        let mut transitions = Vec::new();
+        for transition in file.transitions {
+            check_state(&transition.to)?;
+            for from in &transition.from {
+                check_state(from)?;
+            }
+            let when = transition
+                .when
+                .iter()
+                .map(|text| {
+                    Condition::parse(text).ok_or_else(|| format!("unknown condition {}", text))
+                })
+                .collect::<Result<_, _>>()?;
+
+            transitions.push(Transition {
+                from: transition.from,
+                to: transition.to,
+                when,
+            });
+        }
+
+        Ok(Self {
+            initial: file.initial,
+            states: file.states,
+            transitions,
+            events: file.events,
+        })
+    }
+
+    pub fn state(&self, name: &str) -> Option<&AnimationState> {
+        self.states.get(name)
+    }
+
+    /// Moves the controller into the initial state, or the state its parameters lead to once the
+    /// current state may be left. Returns true when a state was entered.
+    pub fn transition(&self, controller: &mut AnimationController) -> bool {
+        let next = match self.states.get(&controller.state) {
+            None => &self.initial,
+            Some(state) if !state.can_leave(controller.frames_played) => return false,
+            Some(_) => match self.next_state(&controller.state, &controller.params) {
+                Some(next) => next,
+                None => {
+                    // A trigger no transition takes, e.g. casting while surfing, would otherwise
+                    // fire much later, once the character leaves the water
+                    controller.params.casting = false;
+                    return false;
+                }
+            },
+        };
+
+        controller.state = next.clone();
+        controller.frames_played = 0;
+        controller.params.casting = false;
+        true
+    }
+
+    /// The first transition that applies, unless it leads back into the current state without
+    /// being triggered, e.g. casting again while casting restarts the cast
+    fn next_state(&self, current: &str, params: &AnimationParams) -> Option<&String> {
+        self.transitions
+            .iter()
+            .filter(|transition| {
+                transition.from.is_empty() || transition.from.iter().any(|from| from == current)
+            })
+            .find(|transition| transition.when.iter().all(|condition| condition.holds(params)))
+            .filter(|transition| {
+                transition.to != current || transition.when.iter().any(Condition::is_trigger)
+            })
+            .map(|transition| &transition.to)
+    }
+
+    /// Events sent when the frame of the clip starts showing
+    pub fn events(&self, clip: &str, frame: usize) -> &[String] {
+        self.events.get(clip).and_then(|frames| frames.get(&frame)).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walk_finishes_its_step_before_idling() {
+        let file = serde_json::from_str(
+            r#"{
+                "initial": "idle",
+                "states": {
+                    "idle": { "clip": "idle_{direction}" },
+                    "walk": { "clip": "walk_{direction}", "min_frames": 2 }
+                },
+                "transitions": [
+                    { "to": "walk", "when": ["moving"] },
+                    { "to": "idle", "when": ["!moving"] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let machine = StateMachine::from_file(file).unwrap();
+        let mut controller = AnimationController::new("player");
+
+        assert!(machine.transition(&mut controller));
+        assert_eq!(controller.state, "idle");
+
+        controller.params.moving = true;
+        assert!(machine.transition(&mut controller));
+        assert_eq!(controller.state, "walk");
+
+        controller.params.moving = false;
+        controller.frames_played = 1;
+        assert!(!machine.transition(&mut controller));
+        controller.frames_played = 2;
+        assert!(machine.transition(&mut controller));
+        assert_eq!(controller.state, "idle");
+        assert_eq!(
+            machine.state("walk").unwrap().clip_name(Direction::Left),
+            "walk_left"
+        );
+    }
+
+    #[test]
+    fn cast_pressed_while_surfing_is_dropped() {
+        let file = serde_json::from_str(
+            r#"{
+                "initial": "idle",
+                "states": {
+                    "idle": { "clip": "idle_{direction}" },
+                    "cast": { "clip": "cast_{direction}" },
+                    "surf_idle": { "clip": "surf_idle_{direction}" }
+                },
+                "transitions": [
+                    { "to": "cast", "when": ["casting", "!surfing"] },
+                    { "to": "surf_idle", "when": ["surfing"] },
+                    { "to": "idle" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let machine = StateMachine::from_file(file).unwrap();
+        let mut controller = AnimationController::new("player");
+        machine.transition(&mut controller);
+        controller.params.surfing = true;
+        assert!(machine.transition(&mut controller));
+        assert_eq!(controller.state, "surf_idle");
+
+        controller.params.casting = true;
+        assert!(!machine.transition(&mut controller));
+        assert!(!controller.params.casting);
+
+        // Back on land the character doesn't cast by itself
+        controller.params.surfing = false;
+        assert!(machine.transition(&mut controller));
+        assert_eq!(controller.state, "idle");
+    }
+}
@@ -0,0 +1,486 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde::{de::IgnoredAny, Deserialize};
+
+use crate::{
+    assets::{resolve, AssetError, Assets, Handle},
+    atlas::Atlas,
+    spritesheet::{SpriteRegion, Spritesheet, SpritesheetConfig},
+};
+
+mod aseprite;
+mod controller;
+
+pub use controller::{AnimationParams, AnimationState, StateMachine};
+
+/// How long frames are shown when neither the clip nor the frame says otherwise
+const DEFAULT_FRAME_DURATION_MS: u64 = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackMode {
+    #[default]
+    Loop,
+    /// Stops on the last frame and marks the playback finished
+    Once,
+    /// Plays forwards then backwards, without repeating the first and last frame
+    PingPong,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Index of the frame in an Aseprite file, which slices are keyed by
+    pub index: usize,
+    pub region: SpriteRegion,
+    /// Where the region is drawn relative to the sprite position, for trimmed frames
+    pub offset: (i32, i32),
+    /// Drawn mirrored, so e.g. walking right can reuse the frames for walking left
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    pub frames: Vec<Frame>,
+    pub mode: PlaybackMode,
+}
+
+/// Position of a sprite in its current clip
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Playback {
+    pub frame: usize,
+    pub frame_time: Duration,
+    /// Going backwards through a ping-pong clip
+    pub reverse: bool,
+    /// The last frame of a clip played once has been shown for its duration
+    pub finished: bool,
+}
+
+impl AnimationClip {
+    pub fn advance(&self, playback: &mut Playback, delta_time: Duration) {
+        self.advance_with(playback, delta_time, |_| {});
+    }
+
+    /// Advances the playback, calling `on_step` each time a frame has been shown for its whole
+    /// duration, after moving on to the next frame
+    pub fn advance_with(
+        &self,
+        playback: &mut Playback,
+        delta_time: Duration,
+        mut on_step: impl FnMut(&Playback),
+    ) {
+        if self.frames.is_empty() || playback.finished {
+            return;
+        }
+
+        playback.frame_time += delta_time;
+        while let Some(frame) = self.frames.get(playback.frame) {
+            if frame.duration.is_zero() || playback.frame_time < frame.duration {
+                break;
+            }
+            playback.frame_time -= frame.duration;
+            self.step(playback);
+            on_step(playback);
+            if playback.finished {
+                break;
+            }
+        }
+    }
+
+    fn step(&self, playback: &mut Playback) {
+        let last = self.frames.len() - 1;
+        match self.mode {
+            PlaybackMode::Loop => playback.frame = (playback.frame + 1) % self.frames.len(),
+            PlaybackMode::Once if playback.frame >= last => playback.finished = true,
+            PlaybackMode::Once => playback.frame += 1,
+            PlaybackMode::PingPong if last == 0 => {}
+            PlaybackMode::PingPong => {
+                if playback.frame == 0 {
+                    playback.reverse = false;
+                } else if playback.frame == last {
+                    playback.reverse = true;
+                }
+
+                if playback.reverse {
+                    playback.frame -= 1;
+                } else {
+                    playback.frame += 1;
+                }
+            }
+        }
+    }
+
+    pub fn frame(&self, playback: &Playback) -> Option<&Frame> {
+        self.frames.get(playback.frame)
+    }
+
+    /// Time it takes to play every frame once
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+}
+
+/// Named clips of one character or effect, all drawn from the same spritesheet
+#[derive(Debug)]
+pub struct AnimationSet {
+    pub spritesheet: Handle<Spritesheet>,
+    /// Path of the image the frames are cut from, which stays the same when they are drawn from an
+    /// atlas page instead
+    pub image: String,
+    pub clips: HashMap<String, AnimationClip>,
+    /// Named regions such as hitboxes or pivots, from Aseprite slices
+    pub slices: HashMap<String, Vec<SliceKey>>,
+}
+
+/// Region of a slice from a frame onwards, until the next key
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceKey {
+    pub frame: usize,
+    /// Relative to the top left corner of the untrimmed frame
+    pub bounds: SpriteRegion,
+    pub pivot: Option<(i32, i32)>,
+}
+
+impl AnimationSet {
+    /// Loads an animation file, either an Aseprite JSON export or a JSON object
+    /// describing clips on a uniform grid like:
+    ///
+    /// ```json
+    /// {
+    ///   "spritesheet": "../char.png",
+    ///   "sprite_width": 16, "sprite_height": 16, "padding": 1,
+    ///   "clips": {
+    ///     "walk_down": { "frames": [[1, 0], [0, 0]], "frame_duration_ms": 125 },
+    ///     "walk_up": { "frames": [[4, 0], { "x": 4, "y": 0, "flip_x": true }] },
+    ///     "cast": { "frames": [{ "x": 3, "y": 2, "duration_ms": 200 }], "mode": "once" }
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// The spritesheet path is relative to the animation file. Frames are spritesheet cells, with
+    /// an optional duration overriding the clip's `frame_duration_ms`.
+    pub fn load(assets: &mut Assets, path: &str) -> Result<Self, AssetError> {
+        let bytes = assets.read(path)?;
+        let display_path = assets.source().display_path(path);
+        let json_error = |source| AssetError::Json {
+            path: display_path.clone(),
+            source,
+        };
+        let invalid = |reason: String| AssetError::Invalid {
+            path: display_path.clone(),
+            reason,
+        };
+
+        let format: FormatProbe = serde_json::from_slice(&bytes).map_err(json_error)?;
+        if format.meta.is_some() {
+            let file: aseprite::AsepriteFile =
+                serde_json::from_slice(&bytes).map_err(json_error)?;
+            let (clips, slices) = file.animations().map_err(invalid)?;
+            let spritesheet =
+                assets.spritesheet(&resolve(path, file.image()), Default::default())?;
+
+            let set = Self {
+                image: spritesheet.image().path().to_string(),
+                spritesheet,
+                clips,
+                slices,
+            };
+            set.check_regions().map_err(invalid)?;
+            return Ok(set);
+        }
+
+        let file: AnimationFile = serde_json::from_slice(&bytes).map_err(json_error)?;
+        let config = SpritesheetConfig {
+            sprite_width: file.sprite_width,
+            sprite_height: file.sprite_height,
+            padding: file.padding,
+        };
+        let spritesheet = assets.spritesheet(&resolve(path, &file.spritesheet), config)?;
+
+        let mut clips = HashMap::new();
+        for (name, clip) in file.clips {
+            let frame_duration = clip.frame_duration_ms.unwrap_or(DEFAULT_FRAME_DURATION_MS);
+            let mut frames = Vec::new();
+            for (index, frame) in clip.frames.into_iter().enumerate() {
+                let (x, y, duration_ms, flip_x, flip_y) = match frame {
+                    FrameEntry::Cell(x, y) => (x, y, frame_duration, false, false),
+                    FrameEntry::Full {
+                        x,
+                        y,
+                        duration_ms,
+                        flip_x,
+                        flip_y,
+                    } => (x, y, duration_ms.unwrap_or(frame_duration), flip_x, flip_y),
+                };
+                let region = spritesheet.cell(x, y).ok_or_else(|| {
+                    invalid(format!(
+                        "clip {} uses cell ({}, {}) outside the spritesheet",
+                        name, x, y
+                    ))
+                })?;
+                frames.push(Frame {
+                    index,
+                    region,
+                    offset: (0, 0),
+                    flip_x,
+                    flip_y,
+                    duration: Duration::from_millis(duration_ms),
+                });
+            }
+
+            clips.insert(
+                name,
+                AnimationClip {
+                    frames,
+                    mode: clip.mode,
+                },
+            );
+        }
+
+        let set = Self {
+            image: spritesheet.image().path().to_string(),
+            spritesheet,
+            clips,
+            slices: HashMap::new(),
+        };
+        set.check_regions().map_err(invalid)?;
+        Ok(set)
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clips.get(name)
+    }
+
+    /// The key of a slice that applies to the frame
+    pub fn slice(&self, name: &str, frame: &Frame) -> Option<&SliceKey> {
+        self.slices
+            .get(name)?
+            .iter()
+            .filter(|key| key.frame <= frame.index)
+            .max_by_key(|key| key.frame)
+    }
+
+    /// Draws the frames from an atlas page instead of the spritesheet, if the atlas has all of them
+    /// on the same page. Returns whether it does.
+    pub fn use_atlas(&mut self, atlas: &Atlas) -> bool {
+        let mut page = None;
+        for frame in self.clips.values().flat_map(|clip| &clip.frames) {
+            match atlas.region(&self.image, frame.region) {
+                Some(entry) if *page.get_or_insert(entry.page) == entry.page => {}
+                _ => return false,
+            }
+        }
+        let Some(page) = page.and_then(|page| atlas.pages().get(page)) else {
+            return false;
+        };
+
+        for frame in self.clips.values_mut().flat_map(|clip| &mut clip.frames) {
+            if let Some(entry) = atlas.region(&self.image, frame.region) {
+                frame.region = entry.region;
+            }
+        }
+        self.spritesheet = Handle::new(
+            page.path(),
+            Spritesheet::new(page.clone(), Default::default()),
+        );
+        true
+    }
+
+    fn check_regions(&self) -> Result<(), String> {
+        for (name, clip) in &self.clips {
+            if clip.frames.is_empty() {
+                return Err(format!("clip {} has no frames", name));
+            }
+            if clip.frames.iter().any(|frame| frame.duration.is_zero()) {
+                return Err(format!("clip {} has a frame without duration", name));
+            }
+            if clip.frames.iter().any(|frame| self.spritesheet.region(frame.region).is_none()) {
+                return Err(format!("clip {} has a frame outside the spritesheet", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Only Aseprite exports have a `meta` object
+#[derive(Deserialize)]
+struct FormatProbe {
+    meta: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+struct AnimationFile {
+    spritesheet: String,
+    #[serde(default = "default_sprite_size")]
+    sprite_width: u32,
+    #[serde(default = "default_sprite_size")]
+    sprite_height: u32,
+    #[serde(default)]
+    padding: u32,
+    clips: HashMap<String, ClipEntry>,
+}
+
+#[derive(Deserialize)]
+struct ClipEntry {
+    frames: Vec<FrameEntry>,
+    frame_duration_ms: Option<u64>,
+    #[serde(default)]
+    mode: PlaybackMode,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FrameEntry {
+    Cell(u32, u32),
+    Full {
+        x: u32,
+        y: u32,
+        duration_ms: Option<u64>,
+        #[serde(default)]
+        flip_x: bool,
+        #[serde(default)]
+        flip_y: bool,
+    },
+}
+
+fn default_sprite_size() -> u32 {
+    SpritesheetConfig::default().sprite_width
+}
+
+// Resource
+/// Animation sets and state machines by path, loaded once and shared by every sprite using them
+#[derive(Default)]
+pub struct AnimationLibrary {
+    sets: HashMap<String, AnimationSet>,
+    controllers: HashMap<String, StateMachine>,
+}
+
+impl AnimationLibrary {
+    /// Loads the set unless it is already loaded
+    pub fn load(&mut self, assets: &mut Assets, path: &str) -> Result<(), AssetError> {
+        if !self.sets.contains_key(path) {
+            self.sets.insert(path.to_string(), AnimationSet::load(assets, path)?);
+        }
+        Ok(())
+    }
+
+    /// Loads the state machine unless it is already loaded
+    pub fn load_controller(&mut self, assets: &Assets, path: &str) -> Result<(), AssetError> {
+        if !self.controllers.contains_key(path) {
+            self.controllers.insert(path.to_string(), StateMachine::load(assets, path)?);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, path: &str) -> Option<&AnimationSet> {
+        self.sets.get(path)
+    }
+
+    pub fn controller(&self, path: &str) -> Option<&StateMachine> {
+        self.controllers.get(path)
+    }
+
+    /// Whether the file is one of the loaded files or the spritesheets they use
+    pub fn uses(&self, path: &str) -> bool {
+        self.controllers.contains_key(path)
+            || self.sets.iter().any(|(set_path, set)| set_path == path || set.image == path)
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &String> {
+        self.sets.keys().chain(self.controllers.keys())
+    }
+
+    /// Draws the sets whose frames are in the atlas from it
+    pub fn use_atlas(&mut self, atlas: &Atlas) {
+        for set in self.sets.values_mut() {
+            set.use_atlas(atlas);
+        }
+    }
+
+    /// Loads everything again. Files that fail to load keep their previous version.
+    pub fn reload(&mut self, assets: &mut Assets) -> Result<(), AssetError> {
+        let mut result = Ok(());
+        for (path, set) in &mut self.sets {
+            match AnimationSet::load(assets, path) {
+                Ok(reloaded) => *set = reloaded,
+                Err(error) => result = Err(error),
+            }
+        }
+        for (path, controller) in &mut self.controllers {
+            match StateMachine::load(assets, path) {
+                Ok(reloaded) => *controller = reloaded,
+                Err(error) => result = Err(error),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, TILE_ANIMATIONS};
+
+    fn clip(frame_count: u32, mode: PlaybackMode) -> AnimationClip {
+        AnimationClip {
+            frames: (0..frame_count)
+                .map(|x| Frame {
+                    index: x as usize,
+                    region: SpriteRegion {
+                        x: x * 16,
+                        y: 0,
+                        width: 16,
+                        height: 16,
+                    },
+                    offset: (0, 0),
+                    flip_x: false,
+                    flip_y: false,
+                    duration: Duration::from_millis(100),
+                })
+                .collect(),
+            mode,
+        }
+    }
+
+    fn frames_played(clip: &AnimationClip, steps: usize) -> Vec<usize> {
+        let mut playback = Playback::default();
+        (0..steps)
+            .map(|_| {
+                clip.advance(&mut playback, Duration::from_millis(100));
+                playback.frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn playback_modes() {
+        assert_eq!(frames_played(&clip(3, PlaybackMode::Loop), 4), [1, 2, 0, 1]);
+        assert_eq!(frames_played(&clip(3, PlaybackMode::Once), 4), [1, 2, 2, 2]);
+        assert_eq!(
+            frames_played(&clip(3, PlaybackMode::PingPong), 5),
+            [1, 2, 1, 0, 1]
+        );
+
+        let once = clip(2, PlaybackMode::Once);
+        let mut playback = Playback::default();
+        once.advance(&mut playback, Duration::from_millis(150));
+        assert!(!playback.finished);
+        once.advance(&mut playback, Duration::from_millis(50));
+        assert!(playback.finished);
+    }
+
+    #[test]
+    fn cell_far_outside_the_spritesheet_is_rejected() {
+        let mut assets = Assets::new(test_fixtures::source());
+        assert!(AnimationSet::load(&mut assets, TILE_ANIMATIONS).is_ok());
+
+        let source = test_fixtures::modified(TILE_ANIMATIONS, |file| {
+            file["clips"]["shimmer"]["frames"][1] = serde_json::json!([u32::MAX, 0]);
+        });
+        let error =
+            AnimationSet::load(&mut Assets::new(source.clone()), TILE_ANIMATIONS).unwrap_err();
+
+        assert!(matches!(error, AssetError::Invalid { .. }));
+    }
+}
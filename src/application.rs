@@ -1,30 +1,47 @@
 use pixels_engine::components::FireSpell;
+use pixels_engine::components::Inventory;
 use pixels_engine::components::Light;
 use pixels_engine::components::Player;
+use pixels_engine::components::Surf;
 use pixels_engine::systems::cast_spell::CastSpellSystem;
 use pixels_engine::systems::light_control::LightControlSystem;
 use pixels_engine::systems::light_render::LightRenderSystem;
 use pixels_engine::systems::light_render::LightUpdateSystem;
 use pixels_engine::systems::movement::MovementSystem;
+use pixels_engine::systems::post_process::PostProcessSystem;
+use pixels_engine::systems::pushable::PressurePlateSystem;
+use pixels_engine::systems::pushable::PushSystem;
+use pixels_engine::systems::pushable::PushableRenderSystem;
+use pixels_engine::systems::pushable::PushableSpawnSystem;
+use pixels_engine::systems::render_queue::RenderQueueSystem;
 use pixels_engine::systems::spell_effect::SpellEffectRenderSystem;
-use pixels_engine::tile::TileMap;
-use pixels_engine::SCALE_FACTOR;
+use pixels_engine::systems::surf::SurfSystem;
+use pixels_engine::tile::LoadOptions;
 
 use hecs::World;
 use pixels::Pixels;
 use pixels::SurfaceTexture;
+use pixels_engine::assets::AssetError;
+use pixels_engine::assets::Assets;
 use pixels_engine::components::AnimatedSprite;
+use pixels_engine::components::AnimationController;
 use pixels_engine::components::Movement;
 use pixels_engine::components::Position;
-use pixels_engine::components::SpriteType;
+use pixels_engine::config::EngineConfig;
 use pixels_engine::input::Input;
 use pixels_engine::resource::Resources;
+use pixels_engine::resource::PLAYER_ANIMATIONS_PATH;
+use pixels_engine::resource::PLAYER_CONTROLLER_PATH;
 use pixels_engine::systems::camera::CameraFollowSystem;
 use pixels_engine::systems::character_animation::CharacterAnimationSystem;
 use pixels_engine::systems::debug_grid::DebugGridSystem;
+use pixels_engine::systems::hot_reload::HotReloadErrorRenderSystem;
+use pixels_engine::systems::hot_reload::HotReloadSystem;
+use pixels_engine::systems::level_streaming::LevelStreamingSystem;
 use pixels_engine::systems::level_transition::LevelTransitionSystem;
 use pixels_engine::systems::sprite_render::SpriteRenderSystem;
 use pixels_engine::systems::tile_animation::TileAnimationSystem;
+use pixels_engine::systems::tile_render::TileOverlayRenderSystem;
 use pixels_engine::systems::tile_render::TileRenderSystem;
 use pixels_engine::systems::SystemContainer;
 use std::time::Duration;
@@ -40,34 +57,49 @@ pub struct Application {
 }
 
 impl Application {
-    pub fn new(window: &Window) -> Self {
-        let tilemap = TileMap::load("./assets/world.ldtk").unwrap();
-        let player_pos = tilemap.player_starting_position;
+    pub fn new(window: &Window, assets: Assets, config: EngineConfig) -> Result<Self, AssetError> {
+        let resources = Resources::new(
+            config,
+            assets,
+            LoadOptions {
+                lazy: true,
+                prefetch: true,
+            },
+        )?;
+        let player_pos = resources.tilemap.player_starting_position;
 
         let mut world = hecs::World::new();
         world.spawn((
-            AnimatedSprite::new(SpriteType::Player),
+            AnimatedSprite::new(PLAYER_ANIMATIONS_PATH, "idle_down"),
+            AnimationController::new(PLAYER_CONTROLLER_PATH),
             Position::new(player_pos.x, player_pos.y),
             Movement::new(48.0),
             Player,
+            Inventory::default(),
             Light::new(115.0, 0.3, [0.6, 0.6, 1.0]),
-            FireSpell,
+            FireSpell::new("fireball"),
+            Surf,
         ));
 
-        Self {
+        Ok(Self {
             systems: Self::set_up_systems(),
             input: Input::new(),
-            pixels: Self::set_up_pixels_frame_buffer(window),
+            pixels: Self::set_up_pixels_frame_buffer(window, &config),
             delta_time: Duration::ZERO,
             world,
-            resources: Resources::new(tilemap, player_pos),
-        }
+            resources,
+        })
     }
 
     fn set_up_systems() -> SystemContainer {
         let mut systems = SystemContainer::new();
 
+        systems.add_update_system(PushableSpawnSystem);
+        systems.add_update_system(SurfSystem);
+        systems.add_update_system(PushSystem);
         systems.add_update_system(MovementSystem);
+        systems.add_update_system(LevelStreamingSystem);
+        systems.add_update_system(PressurePlateSystem);
         systems.add_update_system(CharacterAnimationSystem);
         systems.add_update_system(TileAnimationSystem);
         systems.add_update_system(CameraFollowSystem);
@@ -75,32 +107,31 @@ impl Application {
         systems.add_update_system(CastSpellSystem);
 
         systems.add_render_system(TileRenderSystem);
+        systems.add_render_system(PushableRenderSystem);
         systems.add_render_system(SpriteRenderSystem);
         systems.add_render_system(SpellEffectRenderSystem);
+        systems.add_render_system(TileOverlayRenderSystem);
+        systems.add_render_system(RenderQueueSystem);
         systems.add_render_system(LightRenderSystem);
         systems.add_render_system(LevelTransitionSystem);
+        systems.add_render_system(PostProcessSystem);
 
         if cfg!(feature = "debug") {
             systems.add_update_system(DebugGridSystem);
             systems.add_update_system(LightControlSystem);
+            systems.add_update_system(HotReloadSystem);
+            systems.add_render_system(HotReloadErrorRenderSystem);
         }
 
         systems
     }
 
-    fn set_up_pixels_frame_buffer(window: &Window) -> Pixels {
+    /// Frame of the configured screen size, scaled up to fill the window
+    fn set_up_pixels_frame_buffer(window: &Window, config: &EngineConfig) -> Pixels {
         let size = window.inner_size();
-        let surface_texture = SurfaceTexture::new(
-            size.width / SCALE_FACTOR,
-            size.height / SCALE_FACTOR,
-            &window,
-        );
-        let mut pixels = Pixels::new(
-            size.width / SCALE_FACTOR,
-            size.height / SCALE_FACTOR,
-            surface_texture,
-        )
-        .unwrap();
+        let surface_texture = SurfaceTexture::new(size.width, size.height, &window);
+        let mut pixels =
+            Pixels::new(config.screen_width, config.screen_height, surface_texture).unwrap();
         pixels.enable_vsync(false);
         pixels
     }
@@ -0,0 +1,57 @@
+use std::fmt;
+
+use crate::tile::TileMapError;
+
+#[derive(Debug)]
+pub enum AssetError {
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    Image {
+        path: String,
+        source: image::ImageError,
+    },
+    Json {
+        path: String,
+        source: serde_json::Error,
+    },
+    /// A file that parses but doesn't describe a valid asset
+    Invalid {
+        path: String,
+        reason: String,
+    },
+    Map(TileMapError),
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::Io { path, source } => write!(f, "Could not read {}: {}", path, source),
+            AssetError::Image { path, source } => {
+                write!(f, "Could not load image {}: {}", path, source)
+            }
+            AssetError::Json { path, source } => write!(f, "Invalid JSON in {}: {}", path, source),
+            AssetError::Invalid { path, reason } => write!(f, "Invalid asset {}: {}", path, reason),
+            AssetError::Map(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AssetError::Io { source, .. } => Some(source),
+            AssetError::Image { source, .. } => Some(source),
+            AssetError::Json { source, .. } => Some(source),
+            AssetError::Invalid { .. } => None,
+            AssetError::Map(error) => error.source(),
+        }
+    }
+}
+
+impl From<TileMapError> for AssetError {
+    fn from(error: TileMapError) -> Self {
+        AssetError::Map(error)
+    }
+}
@@ -0,0 +1,303 @@
+//! Loading of everything in the assets directory. Paths are relative to the root of an
+//! [`AssetSource`], so the game runs from any working directory, and loaded assets are shared
+//! through reference counted [`Handle`]s.
+
+mod error;
+mod source;
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+    ops::Deref,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+pub use error::AssetError;
+pub(crate) use source::{normalize, resolve};
+pub use source::{AssetSource, ASSETS_ENV};
+
+use crate::{
+    spritesheet::{Spritesheet, SpritesheetConfig},
+    tile::{LoadOptions, TileMap, TilesetCache},
+};
+
+pub type Image = image::RgbaImage;
+
+/// Encoded font file, e.g. a TTF
+#[derive(Debug)]
+pub struct Font {
+    pub bytes: Cow<'static, [u8]>,
+}
+
+/// Encoded sound file, e.g. an OGG or WAV
+#[derive(Debug)]
+pub struct Sound {
+    pub bytes: Cow<'static, [u8]>,
+}
+
+/// Shared reference to a loaded asset. The asset stays cached while any handle to it exists.
+pub struct Handle<T> {
+    path: Arc<str>,
+    asset: Arc<T>,
+}
+
+impl<T> Handle<T> {
+    /// Handle to an asset that was made rather than loaded, like an atlas page, and isn't cached
+    pub fn new(path: &str, asset: T) -> Self {
+        Self {
+            path: path.into(),
+            asset: Arc::new(asset),
+        }
+    }
+
+    /// Path the asset was loaded from, relative to the asset root
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            asset: self.asset.clone(),
+        }
+    }
+}
+
+impl<T> Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.asset
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.path).finish()
+    }
+}
+
+/// Cache of one type of asset
+#[derive(Debug)]
+struct Cache<K, T>(HashMap<K, Handle<T>>);
+
+impl<K: Eq + Hash, T> Cache<K, T> {
+    fn get_or_load(
+        &mut self,
+        key: K,
+        path: &str,
+        load: impl FnOnce() -> Result<T, AssetError>,
+    ) -> Result<Handle<T>, AssetError> {
+        if let Some(handle) = self.0.get(&key) {
+            return Ok(handle.clone());
+        }
+
+        let handle = Handle {
+            path: path.into(),
+            asset: Arc::new(load()?),
+        };
+        self.0.insert(key, handle.clone());
+        Ok(handle)
+    }
+
+    /// Drops assets only the cache holds a handle to
+    fn unload_unused(&mut self) {
+        self.0.retain(|_, handle| Arc::strong_count(&handle.asset) > 1);
+    }
+
+    fn forget(&mut self, path: &str) {
+        self.0.retain(|_, handle| &*handle.path != path);
+    }
+}
+
+impl<K, T> Default for Cache<K, T> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+/// Decoded images keyed by path. Clones share the same cache, so tile maps and the prefetch thread
+/// load their tileset images into the one the [`Assets`] resource uses.
+#[derive(Debug, Clone, Default)]
+pub struct ImageCache {
+    source: AssetSource,
+    images: Arc<Mutex<Cache<String, Image>>>,
+}
+
+impl ImageCache {
+    pub fn new(source: AssetSource) -> Self {
+        Self {
+            source,
+            images: Default::default(),
+        }
+    }
+
+    /// Source the images are read from
+    pub fn source(&self) -> &AssetSource {
+        &self.source
+    }
+
+    pub fn load(&self, path: &str) -> Result<Handle<Image>, AssetError> {
+        let path = source::normalize(Path::new(path));
+        if let Some(handle) = self.lock().0.get(&path) {
+            return Ok(handle.clone());
+        }
+
+        // Decoded without holding the lock, so the prefetch thread doesn't block the game thread
+        let bytes = read(&self.source, &path)?;
+        let image = image::load_from_memory(&bytes).map_err(|error| AssetError::Image {
+            path: self.source.display_path(&path),
+            source: error,
+        })?;
+        let handle = Handle::new(&path, image.to_rgba8());
+        Ok(self.lock().0.entry(path).or_insert(handle).clone())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Cache<String, Image>> {
+        // The cache is never left half updated, so a panic on another thread doesn't invalidate it
+        self.images.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Resource
+#[derive(Default)]
+pub struct Assets {
+    images: ImageCache,
+    spritesheets: Cache<(String, SpritesheetConfig), Spritesheet>,
+    fonts: Cache<String, Font>,
+    sounds: Cache<String, Sound>,
+}
+
+impl Assets {
+    pub fn new(source: AssetSource) -> Self {
+        Self {
+            images: ImageCache::new(source),
+            ..Default::default()
+        }
+    }
+
+    pub fn source(&self) -> &AssetSource {
+        self.images.source()
+    }
+
+    pub fn image(&mut self, path: &str) -> Result<Handle<Image>, AssetError> {
+        self.images.load(path)
+    }
+
+    /// Spritesheets are cached per image and config, and share the image with other users of it
+    pub fn spritesheet(
+        &mut self,
+        path: &str,
+        config: SpritesheetConfig,
+    ) -> Result<Handle<Spritesheet>, AssetError> {
+        let path = source::normalize(Path::new(path));
+        if let Some(handle) = self.spritesheets.0.get(&(path.clone(), config)) {
+            return Ok(handle.clone());
+        }
+
+        let image = self.image(&path)?;
+        self.spritesheets.get_or_load((path.clone(), config), &path, || {
+            Ok(Spritesheet::new(image, config))
+        })
+    }
+
+    pub fn font(&mut self, path: &str) -> Result<Handle<Font>, AssetError> {
+        let path = source::normalize(Path::new(path));
+        let source = self.images.source();
+        self.fonts.get_or_load(path.clone(), &path, || {
+            Ok(Font {
+                bytes: read(source, &path)?,
+            })
+        })
+    }
+
+    pub fn sound(&mut self, path: &str) -> Result<Handle<Sound>, AssetError> {
+        let path = source::normalize(Path::new(path));
+        let source = self.images.source();
+        self.sounds.get_or_load(path.clone(), &path, || {
+            Ok(Sound {
+                bytes: read(source, &path)?,
+            })
+        })
+    }
+
+    /// Reads a file without caching it, for assets that are parsed by their users
+    pub fn read(&self, path: &str) -> Result<Cow<'static, [u8]>, AssetError> {
+        read(self.images.source(), path)
+    }
+
+    /// Loads a tile map. Maps are not cached, as the game changes its copy while it runs, but their
+    /// tileset images are shared with the rest of the game.
+    pub fn map(&self, path: &str, options: LoadOptions) -> Result<TileMap, AssetError> {
+        let tilesets = TilesetCache::new(self.images.clone());
+        Ok(TileMap::load_with_tilesets(tilesets, path, options)?)
+    }
+
+    /// Drops every cached asset that nothing outside the cache holds a handle to
+    pub fn unload_unused(&mut self) {
+        // Spritesheets hold handles to their images, so they go first
+        self.spritesheets.unload_unused();
+        self.images.lock().unload_unused();
+        self.fonts.unload_unused();
+        self.sounds.unload_unused();
+    }
+
+    /// Removes a file from the cache so the next load reads it again. Existing handles keep the
+    /// old version.
+    pub fn forget(&mut self, path: &str) {
+        let path = source::normalize(Path::new(path));
+        self.spritesheets.forget(&path);
+        self.images.lock().forget(&path);
+        self.fonts.forget(&path);
+        self.sounds.forget(&path);
+    }
+}
+
+fn read(source: &AssetSource, path: &str) -> Result<Cow<'static, [u8]>, AssetError> {
+    source.read(path).map_err(|error| AssetError::Io {
+        path: source.display_path(path),
+        source: error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, SMALL};
+
+    #[test]
+    fn unused_assets_are_unloaded() {
+        let mut assets = Assets::default();
+        let kept = assets
+            .images
+            .lock()
+            .get_or_load("kept.png".into(), "kept.png", || Ok(Image::new(1, 1)));
+        assets
+            .images
+            .lock()
+            .get_or_load("dropped.png".into(), "dropped.png", || Ok(Image::new(1, 1)))
+            .unwrap();
+
+        assets.unload_unused();
+
+        assert!(assets.images.lock().0.contains_key("kept.png"));
+        assert!(!assets.images.lock().0.contains_key("dropped.png"));
+        drop(kept);
+    }
+
+    #[test]
+    fn maps_share_tileset_images_with_the_game() {
+        let mut assets = Assets::new(test_fixtures::source());
+
+        let tilemap = assets.map(SMALL, LoadOptions::default()).unwrap();
+        let image = assets.image("tiles.png").unwrap();
+
+        let tileset = &tilemap.get_level("start").unwrap().tilesets[&1];
+        assert!(Arc::ptr_eq(&tileset.image.asset, &image.asset));
+    }
+}
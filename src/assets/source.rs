@@ -0,0 +1,134 @@
+use std::{
+    borrow::Cow,
+    env, fs, io,
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Files compiled into the binary by build.rs, empty unless the `embed-assets` feature is enabled
+static EMBEDDED: &[(&str, &[u8])] = include!(concat!(env!("OUT_DIR"), "/embedded_assets.rs"));
+
+/// Environment variable that overrides where assets are read from
+pub const ASSETS_ENV: &str = "PIXELS_ENGINE_ASSETS";
+
+/// Where asset files are read from. Asset paths are relative to the root of the source, e.g.
+/// `world.ldtk` or `tilesets/town.png`.
+#[derive(Debug, Clone)]
+pub enum AssetSource {
+    /// Files in a directory on disk
+    Directory(PathBuf),
+    /// Files compiled into the binary with the `embed-assets` feature
+    Embedded,
+}
+
+impl Default for AssetSource {
+    /// Paths relative to the working directory, or absolute paths
+    fn default() -> Self {
+        AssetSource::Directory(PathBuf::new())
+    }
+}
+
+impl AssetSource {
+    /// Picks the assets of the running game: the embedded assets when they were compiled in,
+    /// otherwise the directory in `PIXELS_ENGINE_ASSETS`, the `assets` directory next to the
+    /// executable or the `assets` directory in the working directory, in that order.
+    pub fn from_env() -> Self {
+        if !EMBEDDED.is_empty() {
+            return AssetSource::Embedded;
+        }
+
+        if let Some(root) = env::var_os(ASSETS_ENV) {
+            return AssetSource::Directory(root.into());
+        }
+
+        let next_to_executable = env::current_exe()
+            .ok()
+            .and_then(|executable| Some(executable.parent()?.join("assets")))
+            .filter(|root| root.is_dir());
+
+        AssetSource::Directory(next_to_executable.unwrap_or_else(|| PathBuf::from("assets")))
+    }
+
+    /// The directory assets are read from, None when they are embedded
+    pub fn directory(&self) -> Option<&Path> {
+        match self {
+            AssetSource::Directory(root) => Some(root),
+            AssetSource::Embedded => None,
+        }
+    }
+
+    pub fn read(&self, path: impl AsRef<Path>) -> io::Result<Cow<'static, [u8]>> {
+        match self {
+            AssetSource::Directory(root) => fs::read(root.join(path)).map(Cow::Owned),
+            AssetSource::Embedded => {
+                let path = normalize(path.as_ref());
+                EMBEDDED
+                    .iter()
+                    .find(|(embedded_path, _)| *embedded_path == path)
+                    .map(|(_, bytes)| Cow::Borrowed(*bytes))
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path))
+            }
+        }
+    }
+
+    pub fn read_to_string(&self, path: impl AsRef<Path>) -> io::Result<String> {
+        String::from_utf8(self.read(path)?.into_owned())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// When the file was last changed, None for embedded assets which never change
+    pub fn modified(&self, path: impl AsRef<Path>) -> Option<SystemTime> {
+        fs::metadata(self.directory()?.join(path)).ok()?.modified().ok()
+    }
+
+    /// Where a file is read from, for error messages
+    pub fn display_path(&self, path: impl AsRef<Path>) -> String {
+        match self {
+            AssetSource::Directory(root) => root.join(path).to_string_lossy().into_owned(),
+            AssetSource::Embedded => format!("embedded:{}", normalize(path.as_ref())),
+        }
+    }
+}
+
+/// Path of a file referenced from inside another asset, e.g. a spritesheet named in an animation
+/// file, relative to the directory of that asset
+pub(crate) fn resolve(base_file: &str, relative: &str) -> String {
+    let directory = Path::new(base_file).parent().unwrap_or(Path::new(""));
+    normalize(&directory.join(relative))
+}
+
+/// Resolves `.` and `..` and uses forward slashes, so the same file always has the same name
+pub(crate) fn normalize(path: &Path) -> String {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_resolves_relative_components() {
+        assert_eq!(normalize(Path::new("./world.ldtk")), "world.ldtk");
+        assert_eq!(
+            normalize(Path::new("maps/../tilesets/./town.tsx")),
+            "tilesets/town.tsx"
+        );
+        assert_eq!(normalize(Path::new("../town.png")), "../town.png");
+        assert_eq!(resolve("animations/player.json", "../char.png"), "char.png");
+    }
+}
@@ -0,0 +1,491 @@
+//! Packs sprite frames and tilesets into a few large pages, so every sprite and tile is drawn from
+//! the same kind of image and the pixels each image doesn't use are not kept in memory.
+//!
+//! Atlases are either packed when the game starts, from the frames of the loaded animation sets
+//! and the tilesets of the loaded levels, or packed ahead of time with the
+//! `pixels-engine-atlas` tool, which writes the pages as PNGs next to a JSON manifest like:
+//!
+//! ```json
+//! {
+//!   "pages": ["atlas_0.png"],
+//!   "entries": [
+//!     { "image": "tilesets/town.png", "page": 0, "region": { "x": 0, "y": 0, "width": 256, "height": 128 } },
+//!     {
+//!       "image": "char.png", "source": { "x": 17, "y": 0, "width": 16, "height": 16 },
+//!       "page": 0, "region": { "x": 0, "y": 128, "width": 16, "height": 16 }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Entries without a `source` hold the whole image. A packed atlas is used instead of the images
+//! it was packed from, so it has to be packed again when they change.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use image::{imageops, ImageError};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    animation::AnimationLibrary,
+    assets::{resolve, AssetError, Assets, Handle, Image},
+    spritesheet::SpriteRegion,
+    tile::TileMap,
+};
+
+pub const DEFAULT_PAGE_SIZE: u32 = 512;
+
+/// Sprite frames and tilesets packed into pages
+#[derive(Debug, Default)]
+pub struct Atlas {
+    pages: Vec<Handle<Image>>,
+    /// Keyed by image path and the packed part of it, None for the whole image
+    entries: HashMap<(String, Option<SpriteRegion>), AtlasEntry>,
+}
+
+/// Where a packed image or part of one is on the pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasEntry {
+    pub page: usize,
+    pub region: SpriteRegion,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    pages: Vec<String>,
+    entries: Vec<ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestEntry {
+    image: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<SpriteRegion>,
+    page: usize,
+    region: SpriteRegion,
+}
+
+impl Atlas {
+    /// Loads an atlas manifest and its pages, which are relative to the manifest
+    pub fn load(assets: &mut Assets, path: &str) -> Result<Self, AssetError> {
+        let bytes = assets.read(path)?;
+        let display_path = assets.source().display_path(path);
+        let manifest: Manifest =
+            serde_json::from_slice(&bytes).map_err(|source| AssetError::Json {
+                path: display_path.clone(),
+                source,
+            })?;
+
+        let pages = manifest
+            .pages
+            .iter()
+            .map(|page| assets.image(&resolve(path, page)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut entries = HashMap::new();
+        for entry in manifest.entries {
+            let region = entry.region;
+            let fits = pages.get(entry.page).is_some_and(|page| {
+                region.x.checked_add(region.width).is_some_and(|right| right <= page.width())
+                    && region
+                        .y
+                        .checked_add(region.height)
+                        .is_some_and(|bottom| bottom <= page.height())
+            });
+            if !fits {
+                return Err(AssetError::Invalid {
+                    path: display_path,
+                    reason: format!("entry for {} is outside its page", entry.image),
+                });
+            }
+            let packed = AtlasEntry {
+                page: entry.page,
+                region: entry.region,
+            };
+            entries.insert((entry.image, entry.source), packed);
+        }
+
+        Ok(Self { pages, entries })
+    }
+
+    /// Writes the pages and the manifest to the directory, as `{name}.json` and `{name}_0.png`,
+    /// `{name}_1.png` and so on
+    pub fn save(&self, directory: &Path, name: &str) -> Result<(), ImageError> {
+        fs::create_dir_all(directory)?;
+
+        let mut pages = Vec::new();
+        for (index, page) in self.pages.iter().enumerate() {
+            let file_name = format!("{}_{}.png", name, index);
+            page.save(directory.join(&file_name))?;
+            pages.push(file_name);
+        }
+
+        let mut entries = self
+            .entries
+            .iter()
+            .map(|((image, source), entry)| ManifestEntry {
+                image: image.clone(),
+                source: *source,
+                page: entry.page,
+                region: entry.region,
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| {
+            let source = entry.source.map(|source| (source.y, source.x));
+            (entry.image.clone(), source)
+        });
+
+        let json = serde_json::to_string_pretty(&Manifest { pages, entries })
+            .expect("the manifest has no maps with non-string keys");
+        fs::write(directory.join(format!("{}.json", name)), json)?;
+        Ok(())
+    }
+
+    pub fn pages(&self) -> &[Handle<Image>] {
+        &self.pages
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Where a region of an image is packed, either on its own or as part of the whole image
+    pub fn region(&self, image: &str, region: SpriteRegion) -> Option<AtlasEntry> {
+        if let Some(entry) = self.entries.get(&(image.to_string(), Some(region))) {
+            return Some(*entry);
+        }
+
+        let whole = self.entries.get(&(image.to_string(), None))?;
+        let fits =
+            region.x.checked_add(region.width).is_some_and(|right| right <= whole.region.width)
+                && region
+                    .y
+                    .checked_add(region.height)
+                    .is_some_and(|bottom| bottom <= whole.region.height);
+        fits.then_some(AtlasEntry {
+            page: whole.page,
+            region: SpriteRegion {
+                x: whole.region.x + region.x,
+                y: whole.region.y + region.y,
+                ..region
+            },
+        })
+    }
+
+    /// Stops handing out the packed copy of an image, e.g. a tileset that changed after packing,
+    /// so it is drawn from its own file
+    pub fn remove_image(&mut self, path: &str) {
+        self.entries.retain(|(image, _), _| image != path);
+    }
+
+    /// Images packed whole, with the page and region they are packed into
+    pub fn images(&self) -> impl Iterator<Item = (&str, &Handle<Image>, SpriteRegion)> {
+        self.entries
+            .iter()
+            .filter(|((_, source), _)| source.is_none())
+            .map(|((image, _), entry)| (image.as_str(), &self.pages[entry.page], entry.region))
+    }
+}
+
+/// Collects images and the parts of them to pack, then packs them into pages. Everything packed
+/// from one image ends up on the same page.
+pub struct AtlasBuilder {
+    page_size: u32,
+    images: BTreeMap<String, Packing>,
+}
+
+struct Packing {
+    image: Handle<Image>,
+    whole: bool,
+    regions: Vec<SpriteRegion>,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self::new(DEFAULT_PAGE_SIZE)
+    }
+}
+
+impl AtlasBuilder {
+    /// Pages are square with sides of the page size, unless an image doesn't fit in one
+    pub fn new(page_size: u32) -> Self {
+        Self {
+            page_size,
+            images: BTreeMap::new(),
+        }
+    }
+
+    /// Packs the whole image, e.g. a tileset
+    pub fn add_image(&mut self, image: &Handle<Image>) {
+        self.packing(image).whole = true;
+    }
+
+    /// Packs parts of the image, e.g. the frames of a spritesheet that are used in animations
+    pub fn add_regions(
+        &mut self,
+        image: &Handle<Image>,
+        regions: impl IntoIterator<Item = SpriteRegion>,
+    ) {
+        let packing = self.packing(image);
+        for region in regions {
+            if !packing.regions.contains(&region) {
+                packing.regions.push(region);
+            }
+        }
+    }
+
+    /// Packs the frames of every loaded animation set
+    pub fn add_animations(&mut self, animations: &AnimationLibrary) {
+        for set in animations.paths().filter_map(|path| animations.get(path)) {
+            let frames = set.clips.values().flat_map(|clip| &clip.frames);
+            self.add_regions(set.spritesheet.image(), frames.map(|frame| frame.region));
+        }
+    }
+
+    /// Packs the tilesets of every loaded level that are still drawn from their own image
+    pub fn add_tilemap(&mut self, tilemap: &TileMap) {
+        for (_, level) in tilemap.levels() {
+            for tileset in level.tilesets.values() {
+                if tileset.image.dimensions() == (tileset.width, tileset.height) {
+                    self.add_image(&tileset.image);
+                }
+            }
+        }
+    }
+
+    fn packing(&mut self, image: &Handle<Image>) -> &mut Packing {
+        self.images.entry(image.path().to_string()).or_insert_with(|| Packing {
+            image: image.clone(),
+            whole: false,
+            regions: Vec::new(),
+        })
+    }
+
+    pub fn build(self) -> Atlas {
+        // Parts of an image packed whole are found through the whole image
+        let mut groups = self
+            .images
+            .into_iter()
+            .map(|(path, packing)| {
+                let mut parts = match packing.whole {
+                    true => vec![(
+                        None,
+                        SpriteRegion {
+                            x: 0,
+                            y: 0,
+                            width: packing.image.width(),
+                            height: packing.image.height(),
+                        },
+                    )],
+                    false => {
+                        packing.regions.into_iter().map(|region| (Some(region), region)).collect()
+                    }
+                };
+                parts.sort_by_key(|(_, region)| std::cmp::Reverse((region.height, region.width)));
+                (path, packing.image, parts)
+            })
+            .collect::<Vec<_>>();
+        // Tall groups first leaves the fewest gaps on shelves
+        groups.sort_by_key(|(_, _, parts)| {
+            std::cmp::Reverse(parts.first().map_or(0, |(_, region)| region.height))
+        });
+
+        let mut shelves: Vec<Shelves> = Vec::new();
+        let mut placed = Vec::new();
+        for (path, image, parts) in groups {
+            let sizes =
+                parts.iter().map(|(_, region)| (region.width, region.height)).collect::<Vec<_>>();
+            let (page, positions) =
+                match shelves.iter_mut().enumerate().find_map(|(index, page)| {
+                    page.place_all(&sizes).map(|positions| (index, positions))
+                }) {
+                    Some(found) => found,
+                    None => {
+                        let widest = sizes.iter().map(|(width, _)| *width).max().unwrap_or(0);
+                        let width = self.page_size.max(widest);
+                        let mut page = Shelves::new(width, self.page_size);
+                        // Images that don't fit in a page get a taller page of their own
+                        let positions = page.place_all(&sizes).unwrap_or_else(|| {
+                            page = Shelves::new(width, u32::MAX);
+                            let positions =
+                                page.place_all(&sizes).expect("the page is wide enough");
+                            page.max_height = page.height;
+                            positions
+                        });
+                        shelves.push(page);
+                        (shelves.len() - 1, positions)
+                    }
+                };
+
+            for ((source, region), (x, y)) in parts.into_iter().zip(positions) {
+                placed.push((path.clone(), image.clone(), source, region, page, x, y));
+            }
+        }
+
+        let mut pages = shelves
+            .iter()
+            .map(|shelves| Image::new(shelves.width, shelves.height.max(1)))
+            .collect::<Vec<_>>();
+        let mut entries = HashMap::new();
+        for (path, image, source, region, page, x, y) in placed {
+            let part = imageops::crop_imm(&*image, region.x, region.y, region.width, region.height);
+            imageops::replace(&mut pages[page], &*part, x as i64, y as i64);
+            let packed = AtlasEntry {
+                page,
+                region: SpriteRegion { x, y, ..region },
+            };
+            entries.insert((path, source), packed);
+        }
+
+        let pages = pages
+            .into_iter()
+            .enumerate()
+            .map(|(index, page)| Handle::new(&format!("atlas_{}.png", index), page))
+            .collect();
+        Atlas { pages, entries }
+    }
+}
+
+/// Rows of rectangles on a page, each as tall as the tallest rectangle in it
+#[derive(Clone)]
+struct Shelves {
+    width: u32,
+    max_height: u32,
+    height: u32,
+    shelves: Vec<Shelf>,
+}
+
+#[derive(Clone, Copy)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    used_width: u32,
+}
+
+impl Shelves {
+    fn new(width: u32, max_height: u32) -> Self {
+        Self {
+            width,
+            max_height,
+            height: 0,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// Places all of the rectangles or none of them
+    fn place_all(&mut self, sizes: &[(u32, u32)]) -> Option<Vec<(u32, u32)>> {
+        let mut page = self.clone();
+        let positions = sizes
+            .iter()
+            .map(|&(width, height)| page.place(width, height))
+            .collect::<Option<_>>()?;
+        *self = page;
+        Some(positions)
+    }
+
+    fn place(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width > self.width {
+            return None;
+        }
+
+        // The shelf that wastes the least height, or a new shelf below the others
+        let page_width = self.width;
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && shelf.used_width + width <= page_width)
+            .min_by_key(|shelf| shelf.height - height);
+        if let Some(shelf) = shelf {
+            let x = shelf.used_width;
+            shelf.used_width += width;
+            return Some((x, shelf.y));
+        }
+
+        if height > self.max_height - self.height {
+            return None;
+        }
+        let y = self.height;
+        self.shelves.push(Shelf {
+            y,
+            height,
+            used_width: width,
+        });
+        self.height += height;
+        Some((0, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(x: u32, y: u32, width: u32, height: u32) -> SpriteRegion {
+        SpriteRegion {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn packs_frames_and_whole_images_without_overlap() {
+        let mut sheet = Image::new(64, 16);
+        sheet.put_pixel(20, 3, image::Rgba([255, 0, 0, 255]));
+        let sheet = Handle::new("char.png", sheet);
+        let tileset = Handle::new("tiles.png", Image::new(48, 32));
+
+        let mut builder = AtlasBuilder::new(64);
+        let frames = [
+            region(0, 0, 16, 16),
+            region(16, 0, 16, 16),
+            region(0, 0, 16, 16),
+        ];
+        builder.add_regions(&sheet, frames);
+        builder.add_image(&tileset);
+        let atlas = builder.build();
+
+        assert_eq!(atlas.pages().len(), 1);
+        let packed = [
+            atlas.region("char.png", region(0, 0, 16, 16)).unwrap(),
+            atlas.region("char.png", region(16, 0, 16, 16)).unwrap(),
+            atlas.region("tiles.png", region(0, 0, 48, 32)).unwrap(),
+        ];
+        for (index, a) in packed.iter().enumerate() {
+            for b in &packed[index + 1..] {
+                let apart = a.region.x + a.region.width <= b.region.x
+                    || b.region.x + b.region.width <= a.region.x
+                    || a.region.y + a.region.height <= b.region.y
+                    || b.region.y + b.region.height <= a.region.y;
+                assert!(apart, "{:?} overlaps {:?}", a, b);
+            }
+        }
+
+        let frame = packed[1].region;
+        let page = &atlas.pages()[0];
+        assert_eq!(page.get_pixel(frame.x + 4, frame.y + 3).0, [255, 0, 0, 255]);
+
+        // Tiles are found within the packed tileset
+        let tile = atlas.region("tiles.png", region(16, 16, 16, 16)).unwrap();
+        assert_eq!(
+            (tile.region.x, tile.region.y),
+            (packed[2].region.x + 16, packed[2].region.y + 16)
+        );
+        assert!(atlas.region("char.png", region(32, 0, 16, 16)).is_none());
+    }
+
+    #[test]
+    fn region_past_the_largest_coordinate_is_not_in_a_whole_image() {
+        let mut builder = AtlasBuilder::new(64);
+        builder.add_image(&Handle::new("tiles.png", Image::new(32, 32)));
+        let atlas = builder.build();
+
+        assert!(atlas.region("tiles.png", region(16, 16, 16, 16)).is_some());
+        assert_eq!(atlas.region("tiles.png", region(u32::MAX, 0, 16, 16)), None);
+        assert_eq!(atlas.region("tiles.png", region(0, 16, 16, u32::MAX)), None);
+    }
+}
@@ -0,0 +1,80 @@
+//! Packs the sprite frames and tilesets of the game into atlas pages ahead of time.
+//!
+//! Usage: pixels-engine-atlas [--page-size N] [directory]
+//!
+//! Without a directory, the atlas is written next to the assets where the game loads it from
+//! instead of packing one at startup. Run it again after changing any of the packed images.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use pixels_engine::{
+    assets::{AssetSource, Assets},
+    atlas::{AtlasBuilder, DEFAULT_PAGE_SIZE},
+    resource::{load_animations, ATLAS_PATH, WORLD_PATH},
+    tile::LoadOptions,
+};
+
+fn main() -> ExitCode {
+    let mut page_size = DEFAULT_PAGE_SIZE;
+    let mut directory = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--page-size" => match args.next().and_then(|size| size.parse().ok()) {
+                Some(size) => page_size = size,
+                None => {
+                    eprintln!("--page-size needs a number of pixels");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "-h" | "--help" => {
+                println!("Usage: pixels-engine-atlas [--page-size N] [directory]");
+                return ExitCode::SUCCESS;
+            }
+            _ => directory = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let mut assets = Assets::new(AssetSource::from_env());
+    let atlas_path = PathBuf::from(ATLAS_PATH);
+    let Some(directory) = directory.or_else(|| {
+        let root = assets.source().directory()?;
+        Some(root.join(atlas_path.parent()?))
+    }) else {
+        eprintln!("The assets are embedded, so the atlas needs a directory to be written to");
+        return ExitCode::FAILURE;
+    };
+
+    // Every level is loaded, so every tileset is packed
+    let packed = assets.map(WORLD_PATH, LoadOptions::default()).and_then(|tilemap| {
+        let animations = load_animations(&mut assets)?;
+        let mut builder = AtlasBuilder::new(page_size);
+        builder.add_animations(&animations);
+        builder.add_tilemap(&tilemap);
+        Ok(builder.build())
+    });
+    let atlas = match packed {
+        Ok(atlas) => atlas,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let name = atlas_path.file_stem().unwrap_or_default().to_string_lossy();
+    if let Err(error) = atlas.save(&directory, &name) {
+        eprintln!(
+            "Could not write the atlas to {}: {}",
+            directory.display(),
+            error
+        );
+        return ExitCode::FAILURE;
+    }
+
+    println!(
+        "Packed {} page(s) into {}",
+        atlas.pages().len(),
+        directory.display()
+    );
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,54 @@
+//! Validates an LDtk project without opening a window.
+//!
+//! Usage: pixels-engine-check [--json] [path]
+//!
+//! Without a path, the world of the game is checked in the same asset directory the game uses.
+//!
+//! Exits with a non-zero code when any issue is found, so it can be used to gate level changes.
+
+use std::process::ExitCode;
+
+use pixels_engine::{
+    assets::AssetSource,
+    resource::WORLD_PATH,
+    validation::{to_json, validate_file},
+};
+
+fn main() -> ExitCode {
+    let mut json = false;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("Usage: pixels-engine-check [--json] [path]");
+                return ExitCode::SUCCESS;
+            }
+            _ => path = Some(arg),
+        }
+    }
+    let source = match path {
+        Some(_) => AssetSource::default(),
+        None => AssetSource::from_env(),
+    };
+    let path = path.unwrap_or_else(|| WORLD_PATH.to_string());
+
+    let issues = validate_file(&source, &path);
+
+    if json {
+        println!("{}", to_json(&issues));
+    } else if issues.is_empty() {
+        println!("{}: no issues found", path);
+    } else {
+        for issue in &issues {
+            println!("{}", issue);
+        }
+        println!("{}: {} issue(s) found", path, issues.len());
+    }
+
+    if issues.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
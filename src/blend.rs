@@ -0,0 +1,45 @@
+/// Blends an RGBA source pixel over a target pixel in the frame buffer. The source alpha is
+/// multiplied by `opacity`, and the target is left fully opaque.
+pub fn blend_pixel(target: &mut [u8], source: &[u8], opacity: f32) {
+    let alpha = source[3] as f32 / 255.0 * opacity;
+    if alpha <= 0.0 {
+        return;
+    }
+
+    if alpha >= 1.0 {
+        target[..4].copy_from_slice(&source[..4]);
+        return;
+    }
+
+    for i in 0..3 {
+        let blended = source[i] as f32 * alpha + target[i] as f32 * (1.0 - alpha);
+        target[i] = blended.round() as u8;
+    }
+    target[3] = 255;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_opaque_pixel_replaces_target() {
+        let mut target = [10, 20, 30, 255];
+        blend_pixel(&mut target, &[200, 100, 50, 255], 1.0);
+        assert_eq!(target, [200, 100, 50, 255]);
+    }
+
+    #[test]
+    fn test_blend_transparent_pixel_keeps_target() {
+        let mut target = [10, 20, 30, 255];
+        blend_pixel(&mut target, &[200, 100, 50, 0], 1.0);
+        assert_eq!(target, [10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn test_blend_half_opacity() {
+        let mut target = [0, 0, 0, 255];
+        blend_pixel(&mut target, &[200, 100, 50, 255], 0.5);
+        assert_eq!(target, [100, 50, 25, 255]);
+    }
+}
@@ -1,4 +1,11 @@
-use crate::{vec2::Vec2, TILE_SIZE};
+use std::time::Duration;
+
+use crate::vec2::Vec2;
+
+/// Largest distance in pixels the screen moves when shaking at full trauma
+const MAX_SHAKE: f32 = 6.0;
+/// Trauma lost per second
+const TRAUMA_DECAY: f32 = 1.5;
 
 #[derive(Debug)]
 pub struct Camera {
@@ -7,6 +14,59 @@ pub struct Camera {
     // Size of the viewport in pixels
     viewport_width: u32,
     viewport_height: u32,
+    /// Added to the position by pans and shakes, so the followed position stays where it is
+    pan: Vec2,
+    shake: Vec2,
+    /// Corners of the area the view stays inside
+    bounds: Option<(Vec2, Vec2)>,
+    /// From 0 to 1, shakes grow with its square
+    trauma: f32,
+    time: f32,
+    active_pan: Option<Pan>,
+    follow: Follow,
+}
+
+/// What the camera follows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Follow {
+    Player,
+    /// Another entity, returning to the player after the duration if there is one
+    Entity(hecs::Entity, Option<Duration>),
+    /// Stays where it is
+    Locked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// Eased progress for linear progress from 0 to 1
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Pan {
+    /// Where the view was when the pan started
+    from: Vec2,
+    /// Where the view ends up, back on the followed position when None
+    to: Option<Vec2>,
+    elapsed: Duration,
+    duration: Duration,
+    easing: Easing,
 }
 
 impl Camera {
@@ -15,9 +75,21 @@ impl Camera {
             position,
             viewport_width,
             viewport_height,
+            pan: Vec2::ZERO,
+            shake: Vec2::ZERO,
+            bounds: None,
+            trauma: 0.0,
+            time: 0.0,
+            active_pan: None,
+            follow: Follow::Player,
         }
     }
 
+    /// Size of the screen in pixels
+    pub fn viewport(&self) -> (u32, u32) {
+        (self.viewport_width, self.viewport_height)
+    }
+
     /// Get camera position
     pub fn position(&self) -> Vec2 {
         self.position
@@ -28,13 +100,62 @@ impl Camera {
         self.position = position;
     }
 
+    /// Position the screen is centred on, including pans and shakes and kept inside the bounds
+    pub fn view_position(&self) -> Vec2 {
+        self.clamp(self.position + self.pan, self.shake)
+    }
+
+    /// Keeps the view inside the area between the corners, e.g. the level, so no space outside it
+    /// is shown. Areas smaller than the screen are centred.
+    pub fn set_bounds(&mut self, min: Vec2, max: Vec2) {
+        self.bounds = Some((min, max));
+    }
+
+    pub fn clear_bounds(&mut self) {
+        self.bounds = None;
+    }
+
+    /// Shakes are clamped too, so they don't show what is outside the bounds. Areas smaller than
+    /// the screen already have empty space around them, so they shake freely.
+    fn clamp(&self, center: Vec2, shake: Vec2) -> Vec2 {
+        let Some((min, max)) = self.bounds else {
+            return center + shake;
+        };
+        let clamp_axis = |center: f32, shake: f32, min: f32, max: f32, viewport: u32| {
+            let half = viewport as f32 / 2.0;
+            if max - min <= viewport as f32 {
+                (min + max) / 2.0 + shake
+            } else {
+                (center + shake).clamp(min + half, max - half)
+            }
+        };
+        Vec2::new(
+            clamp_axis(center.x, shake.x, min.x, max.x, self.viewport_width),
+            clamp_axis(center.y, shake.y, min.y, max.y, self.viewport_height),
+        )
+    }
+
+    /// Moves the camera and any pan along with the world when its origin moves, e.g. when
+    /// streaming into a neighbouring level
+    pub fn move_origin(&mut self, offset: Vec2) {
+        self.position = self.position - offset;
+        if let Some((min, max)) = &mut self.bounds {
+            *min = *min - offset;
+            *max = *max - offset;
+        }
+        if let Some(pan) = &mut self.active_pan {
+            pan.from = pan.from - offset;
+            pan.to = pan.to.map(|to| to - offset);
+        }
+    }
+
     /// Convert world coordinates to screen coordinates
     pub fn world_to_screen(&self, world_pos: Vec2) -> Vec2 {
         let screen_center = Vec2::new(
             self.viewport_width as f32 / 2.0,
             self.viewport_height as f32 / 2.0,
         );
-        world_pos - self.position + screen_center
+        world_pos - self.view_position() + screen_center
     }
 
     /// Convert screen coordinates to world coordinates
@@ -44,15 +165,186 @@ impl Camera {
             self.viewport_height as f32 / 2.0,
         );
         let offset = screen_pos - screen_center;
-        self.position + offset
+        self.view_position() + offset
     }
 
-    /// Check if a world position is visible on screen
-    pub fn is_visible(&self, world_pos: Vec2) -> bool {
+    /// Check if a world position is on screen or within the margin around it, e.g. a tile for
+    /// something a tile in size
+    pub fn is_visible(&self, world_pos: Vec2, margin: f32) -> bool {
         let screen_pos = self.world_to_screen(world_pos);
-        screen_pos.x >= -(TILE_SIZE as f32)
-            && screen_pos.x <= self.viewport_width as f32 + TILE_SIZE as f32
-            && screen_pos.y >= -(TILE_SIZE as f32)
-            && screen_pos.y <= self.viewport_height as f32 + TILE_SIZE as f32
+        screen_pos.x >= -margin
+            && screen_pos.x <= self.viewport_width as f32 + margin
+            && screen_pos.y >= -margin
+            && screen_pos.y <= self.viewport_height as f32 + margin
+    }
+
+    /// Shakes the screen, e.g. 0.3 for a bump or 1 for an explosion. Trauma adds up to 1 and
+    /// wears off over time.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Moves the view to a world position, where it stays until [`Camera::end_pan`]. The followed
+    /// position keeps moving underneath.
+    pub fn pan_to(&mut self, target: Vec2, duration: Duration, easing: Easing) {
+        self.active_pan = Some(Pan {
+            from: self.position + self.pan_offset(),
+            to: Some(target),
+            elapsed: Duration::ZERO,
+            duration,
+            easing,
+        });
+    }
+
+    /// Moves the view back to the followed position
+    pub fn end_pan(&mut self, duration: Duration, easing: Easing) {
+        if self.active_pan.is_some() {
+            self.active_pan = Some(Pan {
+                from: self.position + self.pan_offset(),
+                to: None,
+                elapsed: Duration::ZERO,
+                duration,
+                easing,
+            });
+        }
+    }
+
+    pub fn is_panning(&self) -> bool {
+        self.active_pan.is_some_and(|pan| pan.elapsed < pan.duration)
+    }
+
+    pub fn follow(&self) -> Follow {
+        self.follow
+    }
+
+    /// Follows the player again
+    pub fn unlock(&mut self) {
+        self.follow = Follow::Player;
+    }
+
+    /// Stops following anything
+    pub fn lock(&mut self) {
+        self.follow = Follow::Locked;
+    }
+
+    /// Follows another entity, for the duration or until the camera is unlocked
+    pub fn focus(&mut self, entity: hecs::Entity, duration: Option<Duration>) {
+        self.follow = Follow::Entity(entity, duration);
+    }
+
+    /// Advances shakes, pans and focus. Run once per update after the position is followed.
+    pub fn update_effects(&mut self, delta_time: Duration) {
+        let seconds = delta_time.as_secs_f32();
+        self.time += seconds;
+        self.trauma = (self.trauma - TRAUMA_DECAY * seconds).max(0.0);
+
+        if let Follow::Entity(_, Some(remaining)) = &mut self.follow {
+            *remaining = remaining.saturating_sub(delta_time);
+            if remaining.is_zero() {
+                self.follow = Follow::Player;
+            }
+        }
+
+        if let Some(pan) = &mut self.active_pan {
+            pan.elapsed = (pan.elapsed + delta_time).min(pan.duration);
+            if pan.to.is_none() && pan.elapsed >= pan.duration {
+                self.active_pan = None;
+            }
+        }
+
+        self.pan = self.pan_offset();
+        // Whole pixels, so a shaking screen doesn't blur pixel art
+        let shake = self.shake_offset();
+        self.shake = Vec2::new(shake.x.round(), shake.y.round());
+    }
+
+    fn pan_offset(&self) -> Vec2 {
+        let Some(pan) = self.active_pan else {
+            return Vec2::ZERO;
+        };
+        let t = match pan.duration.is_zero() {
+            true => 1.0,
+            false => pan.easing.apply(pan.elapsed.as_secs_f32() / pan.duration.as_secs_f32()),
+        };
+        let to = pan.to.unwrap_or(self.position);
+        pan.from + (to - pan.from) * t - self.position
+    }
+
+    /// Smooth noise from sines of unrelated frequencies, so the shake doesn't look regular
+    fn shake_offset(&self) -> Vec2 {
+        let amount = MAX_SHAKE * self.trauma * self.trauma;
+        let t = self.time;
+        let x = ((t * 53.0).sin() + (t * 31.0 + 1.7).sin()) / 2.0;
+        let y = ((t * 47.0 + 0.6).sin() + (t * 29.0 + 2.9).sin()) / 2.0;
+        Vec2::new(x, y) * amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pans_hold_on_the_target_until_ended() {
+        let mut camera = Camera::new(Vec2::new(0.0, 0.0), 160, 144);
+        camera.pan_to(
+            Vec2::new(100.0, 0.0),
+            Duration::from_secs(1),
+            Easing::Linear,
+        );
+
+        camera.update_effects(Duration::from_millis(500));
+        assert_eq!(camera.view_position(), Vec2::new(50.0, 0.0));
+        camera.update_effects(Duration::from_secs(2));
+        assert_eq!(camera.view_position(), Vec2::new(100.0, 0.0));
+
+        // The followed position moves underneath without moving the view
+        camera.set_position(Vec2::new(10.0, 0.0));
+        camera.update_effects(Duration::ZERO);
+        assert_eq!(camera.view_position(), Vec2::new(100.0, 0.0));
+        assert_eq!(
+            camera.world_to_screen(Vec2::new(100.0, 0.0)),
+            Vec2::new(80.0, 72.0)
+        );
+
+        camera.end_pan(Duration::from_secs(1), Easing::Linear);
+        camera.update_effects(Duration::from_secs(1));
+        assert_eq!(camera.view_position(), Vec2::new(10.0, 0.0));
+        assert!(!camera.is_panning());
+    }
+
+    #[test]
+    fn view_stays_inside_bounds_and_centres_small_levels() {
+        let mut camera = Camera::new(Vec2::new(10.0, 10.0), 160, 144);
+        camera.set_bounds(Vec2::new(0.0, 0.0), Vec2::new(320.0, 96.0));
+        assert_eq!(camera.view_position(), Vec2::new(80.0, 48.0));
+
+        camera.set_position(Vec2::new(400.0, 0.0));
+        assert_eq!(camera.view_position(), Vec2::new(240.0, 48.0));
+        assert_eq!(
+            camera.world_to_screen(Vec2::new(320.0, 96.0)),
+            Vec2::new(160.0, 120.0)
+        );
+    }
+
+    #[test]
+    fn shakes_stay_inside_bounds() {
+        let mut camera = Camera::new(Vec2::new(80.0, 72.0), 160, 144);
+        camera.set_bounds(Vec2::new(0.0, 0.0), Vec2::new(320.0, 288.0));
+        camera.add_trauma(1.0);
+
+        let mut shook = false;
+        for _ in 0..10 {
+            camera.update_effects(Duration::from_millis(16));
+            let view = camera.view_position();
+            assert!(view.x >= 80.0 && view.y >= 72.0, "{:?}", view);
+            shook |= view != Vec2::new(80.0, 72.0);
+        }
+        // Shakes towards the inside of the bounds are kept
+        assert!(shook);
     }
 }
@@ -1,84 +1,100 @@
-use lazy_static::lazy_static;
-use std::time::Duration;
+use std::{collections::HashSet, time::Duration};
 
+use crate::animation::{AnimationParams, Playback};
 use crate::input::Input;
 use crate::movement_util::{Axis, Direction};
+use crate::spritesheet::DrawParams;
 
 use crate::vec2::Vec2;
 
+/// Sprite drawn from a clip of an animation set
 #[derive(Debug, Default)]
 pub struct AnimatedSprite {
-    pub sprite_type: SpriteType,
-    pub current_animation_frame: usize,
-    pub frame_time: f32,
-}
-
-pub mod player_sprite_positions {
-    pub const IDLE_DOWN: &[(u32, u32)] = &[(1, 0)];
-    pub const IDLE_UP: &[(u32, u32)] = &[(4, 0)];
-    pub const IDLE_LEFT: &[(u32, u32)] = &[(6, 0)];
-    pub const IDLE_RIGHT: &[(u32, u32)] = &[(8, 0)];
-
-    pub const WALK_DOWN: &[(u32, u32)] = &[(1, 0), (0, 0), (1, 0), (2, 0)];
-    pub const WALK_UP: &[(u32, u32)] = &[(4, 0), (3, 0), (4, 0), (5, 0)];
-    pub const WALK_LEFT: &[(u32, u32)] = &[(6, 0), (7, 0)];
-    pub const WALK_RIGHT: &[(u32, u32)] = &[(8, 0), (9, 0)];
+    /// Path of the animation set in the `AnimationLibrary`
+    pub animations: String,
+    pub clip: String,
+    pub playback: Playback,
+    /// Flip, tint, opacity and palette of the whole sprite
+    pub params: DrawParams,
 }
 
 impl AnimatedSprite {
-    pub fn new(sprite_type: SpriteType) -> Self {
+    pub fn new(animations: &str, clip: &str) -> Self {
         Self {
-            sprite_type,
+            animations: animations.to_string(),
+            clip: clip.to_string(),
             ..Default::default()
         }
     }
 
-    pub fn get_sprite_frames(&self, direction: &Direction, is_moving: bool) -> &[(u32, u32)] {
-        match (&self.sprite_type, direction, is_moving) {
-            (SpriteType::Player, Direction::Down, false) => player_sprite_positions::IDLE_DOWN,
-            (SpriteType::Player, Direction::Up, false) => player_sprite_positions::IDLE_UP,
-            (SpriteType::Player, Direction::Left, false) => player_sprite_positions::IDLE_LEFT,
-            (SpriteType::Player, Direction::Right, false) => player_sprite_positions::IDLE_RIGHT,
-            (SpriteType::Player, Direction::Left, true) => player_sprite_positions::WALK_LEFT,
-            (SpriteType::Player, Direction::Right, true) => player_sprite_positions::WALK_RIGHT,
-            (SpriteType::Player, Direction::Up, true) => player_sprite_positions::WALK_UP,
-            (SpriteType::Player, Direction::Down, true) => player_sprite_positions::WALK_DOWN,
+    /// Switches to a clip, starting it from the beginning unless it is already playing
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            self.clip = clip.to_string();
+            self.playback = Playback::default();
         }
     }
 
-    pub fn get_current_frame(&self, direction: &Direction, is_moving: bool) -> (u32, u32) {
-        let frames = self.get_sprite_frames(direction, is_moving);
-        frames[self.current_animation_frame % frames.len()]
+    /// Starts a clip from the beginning, even if it is already playing
+    pub fn restart(&mut self, clip: &str) {
+        self.clip = clip.to_string();
+        self.playback = Playback::default();
     }
 }
 
+/// Picks the clip of the entity's `AnimatedSprite` with a state machine from the
+/// `AnimationLibrary`
 #[derive(Debug, Default)]
-pub enum SpriteType {
-    #[default]
-    Player,
+pub struct AnimationController {
+    /// Path of the state machine in the `AnimationLibrary`
+    pub machine: String,
+    pub params: AnimationParams,
+    /// Current state, empty until the initial state is entered
+    pub state: String,
+    /// Frames shown since the state was entered
+    pub frames_played: usize,
+}
+
+impl AnimationController {
+    pub fn new(machine: &str) -> Self {
+        Self {
+            machine: machine.to_string(),
+            ..Default::default()
+        }
+    }
 }
 
 pub type Position = Vec2;
 
+/// Tiles are `tile_size` pixels square, the tile size of the [`EngineConfig`]
+///
+/// [`EngineConfig`]: crate::config::EngineConfig
 impl Position {
-    pub fn at_tile(tile_x: i64, tile_y: i64) -> Self {
+    pub fn at_tile(tile_x: i64, tile_y: i64, tile_size: u32) -> Self {
         Self::new(
-            tile_x as f32 * crate::TILE_SIZE as f32,
-            tile_y as f32 * crate::TILE_SIZE as f32,
+            tile_x as f32 * tile_size as f32,
+            tile_y as f32 * tile_size as f32,
         )
     }
 
-    pub fn tile(&self) -> (i64, i64) {
+    pub fn tile(&self, tile_size: u32) -> (i64, i64) {
         (
-            (self.x / crate::TILE_SIZE as f32) as i64,
-            (self.y / crate::TILE_SIZE as f32) as i64,
+            (self.x / tile_size as f32) as i64,
+            (self.y / tile_size as f32) as i64,
         )
     }
 
-    pub fn aligned_tile(&self) -> Option<(i64, i64)> {
+    pub fn nearest_tile(&self, tile_size: u32) -> (i64, i64) {
+        (
+            (self.x / tile_size as f32).round() as i64,
+            (self.y / tile_size as f32).round() as i64,
+        )
+    }
+
+    pub fn aligned_tile(&self, tile_size: u32) -> Option<(i64, i64)> {
         // Check if position is exactly aligned with tile grid
-        if self.x % crate::TILE_SIZE as f32 == 0.0 && self.y % crate::TILE_SIZE as f32 == 0.0 {
-            Some(self.tile())
+        if self.x % tile_size as f32 == 0.0 && self.y % tile_size as f32 == 0.0 {
+            Some(self.tile(tile_size))
         } else {
             None
         }
@@ -90,6 +106,9 @@ pub struct Movement {
     pub speed: f32,
     pub direction: Direction,
     pub is_moving: bool,
+    pub mode: MovementMode,
+    // Tile the entity is being moved to regardless of input, e.g. when hopping onto water
+    pub forced_step: Option<(i64, i64)>,
 
     // These are used to apply a delay before starting movement
     pub start_delay: Duration,
@@ -117,7 +136,30 @@ impl Movement {
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum MovementMode {
+    #[default]
+    Walking,
+    Surfing,
+}
+
 pub struct Player;
+
+#[derive(Debug, Default)]
+pub struct Inventory {
+    items: HashSet<String>,
+}
+
+impl Inventory {
+    pub fn add(&mut self, item: &str) {
+        self.items.insert(item.to_string());
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        self.items.contains(item)
+    }
+}
+
 pub struct PlayerStartingPosition(pub Position);
 
 pub struct Light {
@@ -136,38 +178,30 @@ impl Light {
     }
 }
 
-pub struct FireSpell;
-
-#[derive(Debug, Clone)]
-pub struct SpellEffect {
-    pub effect_type: SpellEffectType,
-    pub current_frame: usize,
-    pub frame_time: f32,
-    pub is_finished: bool,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum SpellEffectType {
-    Fireball,
+/// Lets the entity cast a spell, whose effect plays a clip of the effects animation set
+pub struct FireSpell {
+    pub clip: String,
 }
 
-impl SpellEffect {
-    pub fn new(effect_type: SpellEffectType) -> Self {
+impl FireSpell {
+    pub fn new(clip: &str) -> Self {
         Self {
-            effect_type,
-            current_frame: 0,
-            frame_time: 0.0,
-            is_finished: false,
+            clip: clip.to_string(),
         }
     }
+}
 
-    pub fn get_sprite_frames(&self) -> &[(u32, u32)] {
-        match self.effect_type {
-            SpellEffectType::Fireball => FIREBALL_FRAMES.as_slice(),
-        }
-    }
+/// Allows the entity to surf on water tiles
+pub struct Surf;
+
+/// Entity that occupies a tile and slides one tile when the player walks into it
+pub struct Pushable {
+    // Id of the LDtk entity, used to remember where it was left
+    pub entity_id: String,
 }
 
-lazy_static! {
-    static ref FIREBALL_FRAMES: Vec<(u32, u32)> = (0..=10).map(|x| (x, 0)).collect();
+#[derive(Debug, Clone)]
+pub struct SpellEffect {
+    /// Clip of the effect in the effects animation set
+    pub clip: String,
 }
@@ -0,0 +1,111 @@
+//! Settings a game chooses when it starts, like the size of the screen and of its tiles
+
+use std::io::ErrorKind;
+
+use serde::Deserialize;
+
+use crate::assets::{AssetError, Assets};
+
+// Resource
+/// Resolution and grid of the game. Every renderer draws into a frame of the screen size, and
+/// movement steps from tile to tile of the tile size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    /// Size of the frame in pixels, before it is scaled up to the window
+    pub screen_width: u32,
+    pub screen_height: u32,
+    /// Size of a tile in pixels, which has to match the grid of the levels
+    pub tile_size: u32,
+    /// Pixels of the window per pixel of the frame when the window opens
+    pub scale_factor: u32,
+}
+
+/// Game Boy screen with 16px tiles
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            screen_width: 160,
+            screen_height: 144,
+            tile_size: 16,
+            scale_factor: 4,
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Reads the config from a JSON file, e.g. `{ "screen_width": 240, "screen_height": 160,
+    /// "tile_size": 8 }`. Missing settings and a missing file use the defaults.
+    pub fn load(assets: &Assets, path: &str) -> Result<Self, AssetError> {
+        let bytes = match assets.read(path) {
+            Ok(bytes) => bytes,
+            Err(AssetError::Io { source, .. }) if source.kind() == ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(error) => return Err(error),
+        };
+        let display_path = assets.source().display_path(path);
+        let config: Self = serde_json::from_slice(&bytes).map_err(|source| AssetError::Json {
+            path: display_path.clone(),
+            source,
+        })?;
+
+        let sizes = [
+            config.screen_width,
+            config.screen_height,
+            config.tile_size,
+            config.scale_factor,
+        ];
+        if sizes.contains(&0) {
+            return Err(AssetError::Invalid {
+                path: display_path,
+                reason: "sizes have to be at least 1".to_string(),
+            });
+        }
+        Ok(config)
+    }
+
+    /// Offset from a position to the centre of the tile it is the top left corner of
+    pub fn half_tile(&self) -> f32 {
+        self.tile_size as f32 / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    #[test]
+    fn missing_file_uses_the_defaults() {
+        let assets = Assets::new(test_fixtures::source());
+
+        let config = EngineConfig::load(&assets, "engine.json").unwrap();
+
+        assert_eq!(config, EngineConfig::default());
+    }
+
+    #[test]
+    fn missing_settings_use_the_defaults() {
+        let source = test_fixtures::copy_with("engine.json", r#"{ "tile_size": 8 }"#);
+        let assets = Assets::new(source.clone());
+
+        let config = EngineConfig::load(&assets, "engine.json").unwrap();
+
+        let expected = EngineConfig {
+            tile_size: 8,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn zero_size_is_rejected() {
+        let source = test_fixtures::copy_with("engine.json", r#"{ "screen_width": 0 }"#);
+        let assets = Assets::new(source.clone());
+
+        let error = EngineConfig::load(&assets, "engine.json").unwrap_err();
+
+        assert!(matches!(error, AssetError::Invalid { .. }), "{}", error);
+    }
+}
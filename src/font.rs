@@ -0,0 +1,139 @@
+//! A tiny 3x5 pixel font for debug text drawn straight into the frame buffer
+
+pub const GLYPH_WIDTH: u32 = 3;
+pub const GLYPH_HEIGHT: u32 = 5;
+/// Horizontal distance between the start of two characters
+pub const ADVANCE: u32 = GLYPH_WIDTH + 1;
+/// Vertical distance between the start of two lines
+pub const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 1;
+
+/// Each row of a glyph is 3 bits, the most significant bit is the leftmost pixel
+fn glyph(character: char) -> [u8; 5] {
+    match character.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' | '\\' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '(' | '[' | '{' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' | ']' | '}' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '"' => [0b101, 0b101, 0b000, 0b000, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        ' ' => [0b000; 5],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+/// Draws a single line of text with its top left corner at the given position in a frame of the
+/// given width
+pub fn draw_text(frame: &mut [u8], frame_width: u32, text: &str, x: i64, y: i64, color: [u8; 4]) {
+    let frame_height = (frame.len() / 4) as i64 / frame_width.max(1) as i64;
+    for (index, character) in text.chars().enumerate() {
+        let glyph_x = x + index as i64 * ADVANCE as i64;
+
+        for (row, bits) in glyph(character).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+
+                let pixel_x = glyph_x + column as i64;
+                let pixel_y = y + row as i64;
+                if pixel_x < 0
+                    || pixel_y < 0
+                    || pixel_x >= frame_width as i64
+                    || pixel_y >= frame_height
+                {
+                    continue;
+                }
+
+                let index = ((pixel_y * frame_width as i64 + pixel_x) * 4) as usize;
+                frame[index..index + 4].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+/// Splits text into lines that fit in a frame of the given width when drawn with [`draw_text`]
+pub fn wrap_text(text: &str, frame_width: u32) -> Vec<String> {
+    let max_chars = (frame_width / ADVANCE).max(1) as usize;
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_chars {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+
+            // Words longer than a line, like file paths, are broken anywhere
+            while line.chars().count() > max_chars {
+                let rest = line.chars().skip(max_chars).collect();
+                lines.push(line.chars().take(max_chars).collect());
+                line = rest;
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_text_breaks_on_words_and_long_words() {
+        let long_word = "a".repeat(45);
+        let lines = wrap_text(&format!("hello world {}", long_word), 160);
+
+        assert_eq!(lines[0], "hello world");
+        assert_eq!(lines[1].len(), 40);
+        assert_eq!(lines[2].len(), 5);
+    }
+}
@@ -9,6 +9,7 @@ pub struct Input {
     shift: bool,
     j: bool,
     k: bool,
+    e: bool,
     space: bool,
 }
 
@@ -54,6 +55,10 @@ impl Input {
                         self.k = is_pressed;
                         true
                     }
+                    VirtualKeyCode::E => {
+                        self.e = is_pressed;
+                        true
+                    }
                     VirtualKeyCode::Space => {
                         self.space = is_pressed;
                         true
@@ -69,6 +74,14 @@ impl Input {
         }
     }
 
+    /// Input with a direction key held down, for running systems in tests
+    #[cfg(test)]
+    pub(crate) fn holding(direction: Direction) -> Self {
+        let mut input = Self::new();
+        input.handle_direction_key(direction, true);
+        input
+    }
+
     fn handle_direction_key(&mut self, direction: Direction, is_pressed: bool) {
         if is_pressed {
             // Remove any existing instance of this direction
@@ -125,6 +138,10 @@ impl Input {
         self.k
     }
 
+    pub fn e(&self) -> bool {
+        self.e
+    }
+
     pub fn space(&self) -> bool {
         self.space
     }
@@ -1,5 +1,11 @@
+pub mod animation;
+pub mod assets;
+pub mod atlas;
+pub mod blend;
 pub mod camera;
 pub mod components;
+pub mod config;
+pub mod font;
 pub mod fps_counter;
 pub mod input;
 pub mod ivec2;
@@ -7,10 +13,8 @@ pub mod movement_util;
 pub mod resource;
 pub mod spritesheet;
 pub mod systems;
+#[cfg(test)]
+mod test_fixtures;
 pub mod tile;
+pub mod validation;
 pub mod vec2;
-
-pub const SCREEN_WIDTH: u32 = 160;
-pub const SCREEN_HEIGHT: u32 = 144;
-pub const TILE_SIZE: u32 = 16;
-pub const SCALE_FACTOR: u32 = 4;
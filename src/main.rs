@@ -3,8 +3,10 @@ use std::time::Instant;
 
 use application::Application;
 use pixels::Error;
+use pixels_engine::assets::{AssetSource, Assets};
+use pixels_engine::config::EngineConfig;
 use pixels_engine::fps_counter::FpsCounter;
-use pixels_engine::{SCALE_FACTOR, SCREEN_HEIGHT, SCREEN_WIDTH};
+use pixels_engine::resource::ENGINE_CONFIG_PATH;
 use winit::dpi::LogicalSize;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
@@ -14,11 +16,20 @@ mod application;
 
 fn main() -> Result<(), Error> {
     env_logger::init();
+    let assets = Assets::new(AssetSource::from_env());
+    let config = match EngineConfig::load(&assets, ENGINE_CONFIG_PATH) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Failed to load engine config: {}", error);
+            std::process::exit(1);
+        }
+    };
+
     let event_loop = EventLoop::new();
     let window = {
         let size = LogicalSize::new(
-            (SCREEN_WIDTH * SCALE_FACTOR) as f64,
-            (SCREEN_HEIGHT * SCALE_FACTOR) as f64,
+            (config.screen_width * config.scale_factor) as f64,
+            (config.screen_height * config.scale_factor) as f64,
         );
 
         WindowBuilder::new()
@@ -29,7 +40,13 @@ fn main() -> Result<(), Error> {
             .unwrap()
     };
 
-    let mut application = Application::new(&window);
+    let mut application = match Application::new(&window, assets, config) {
+        Ok(application) => application,
+        Err(error) => {
+            eprintln!("Failed to load assets: {}", error);
+            std::process::exit(1);
+        }
+    };
 
     let mut fps_counter = FpsCounter::new(24000);
 
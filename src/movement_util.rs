@@ -1,14 +1,15 @@
+use std::str::FromStr;
+
 use crate::vec2::Vec2;
-use crate::TILE_SIZE;
 
 pub trait PositionExt {
-    fn tile_coordinate(&self) -> (i64, i64);
+    fn tile_coordinate(&self, tile_size: u32) -> (i64, i64);
 }
 
 impl PositionExt for Vec2 {
-    fn tile_coordinate(&self) -> (i64, i64) {
-        let x = (self.x / TILE_SIZE as f32).floor() as i64;
-        let y = (self.y / TILE_SIZE as f32).floor() as i64;
+    fn tile_coordinate(&self, tile_size: u32) -> (i64, i64) {
+        let x = (self.x / tile_size as f32).floor() as i64;
+        let y = (self.y / tile_size as f32).floor() as i64;
         (x, y)
     }
 }
@@ -23,6 +24,16 @@ pub enum Direction {
 }
 
 impl Direction {
+    /// Lowercase name, used in animation clip names like `walk_down`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Left => "left",
+            Direction::Right => "right",
+        }
+    }
+
     pub fn to_vector(&self) -> Vec2 {
         match self {
             Direction::Up => Vec2::new(0.0, -1.0),
@@ -65,7 +76,24 @@ impl Direction {
         }
     }
 
-    pub fn from_str(string: &str) -> Result<Self, String> {
+    pub fn rotated(&self, rotation: Rotation) -> Self {
+        match (self, rotation) {
+            (Direction::Up, Rotation::Clockwise) => Direction::Right,
+            (Direction::Right, Rotation::Clockwise) => Direction::Down,
+            (Direction::Down, Rotation::Clockwise) => Direction::Left,
+            (Direction::Left, Rotation::Clockwise) => Direction::Up,
+            (Direction::Up, Rotation::CounterClockwise) => Direction::Left,
+            (Direction::Left, Rotation::CounterClockwise) => Direction::Down,
+            (Direction::Down, Rotation::CounterClockwise) => Direction::Right,
+            (Direction::Right, Rotation::CounterClockwise) => Direction::Up,
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
         match string {
             "Up" => Ok(Direction::Up),
             "Down" => Ok(Direction::Down),
@@ -76,6 +104,12 @@ impl Direction {
     }
 }
 
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Rotation {
+    Clockwise,
+    CounterClockwise,
+}
+
 #[derive(PartialEq, Debug)]
 pub enum Axis {
     Horizontal,
@@ -1,63 +1,146 @@
+use std::{collections::HashSet, io::ErrorKind};
+
 use crate::{
+    animation::AnimationLibrary,
+    assets::{AssetError, Assets},
+    atlas::{Atlas, AtlasBuilder},
     camera::Camera,
-    spritesheet::{Spritesheet, SpritesheetConfig},
-    systems::level_transition::ScreenTransition,
-    tile::TileMap,
+    config::EngineConfig,
+    systems::{
+        camera::fit_to_level, character_animation::AnimationEvents, hot_reload::HotReloadState,
+        level_transition::ScreenTransition, post_process::PostProcessStack,
+        pushable::PushableState, render_queue::RenderQueue,
+    },
+    tile::{LoadOptions, TileMap},
     vec2::Vec2,
-    SCREEN_HEIGHT, SCREEN_WIDTH, TILE_SIZE,
 };
 
 pub struct Resources {
+    pub config: EngineConfig,
+    pub assets: Assets,
     pub camera: Camera,
-    pub character_spritesheet: CharacterSpritesheet,
-    pub effects_spritesheet: EffectsSpritesheet,
+    pub render_queue: RenderQueue,
+    pub animations: AnimationLibrary,
+    pub animation_events: AnimationEvents,
     pub tilemap: TileMap,
+    /// How the world was loaded, so it is loaded the same way when it changes on disk
+    pub load_options: LoadOptions,
+    /// Pages the tilesets and animation frames are drawn from
+    pub atlas: Atlas,
     pub screen_transition: ScreenTransition,
+    pub post_process: PostProcessStack,
     pub light_map: LightMap,
+    pub game_flags: GameFlags,
+    pub pushable_state: PushableState,
+    pub hot_reload: HotReloadState,
 }
 
+// Paths relative to the asset root
+/// Resolution and tile size, read by [`EngineConfig::load`]
+pub const ENGINE_CONFIG_PATH: &str = "engine.json";
+pub const WORLD_PATH: &str = "world.ldtk";
+pub const PLAYER_ANIMATIONS_PATH: &str = "animations/player.json";
+pub const PLAYER_CONTROLLER_PATH: &str = "animations/player.controller.json";
+pub const EFFECT_ANIMATIONS_PATH: &str = "animations/effects.json";
+/// Atlas packed ahead of time by `pixels-engine-atlas`. Without it, one is packed at startup.
+pub const ATLAS_PATH: &str = "atlas/atlas.json";
+
 impl Resources {
-    pub fn new(tilemap: TileMap, player_pos: Vec2) -> Self {
-        let camera = Camera::new(
-            player_pos + Vec2::new(TILE_SIZE as f32 / 2.0, TILE_SIZE as f32 / 2.0),
-            SCREEN_WIDTH,
-            SCREEN_HEIGHT,
+    pub fn new(
+        config: EngineConfig,
+        mut assets: Assets,
+        load_options: LoadOptions,
+    ) -> Result<Self, AssetError> {
+        let mut tilemap = load_world(&config, &assets, load_options)?;
+        let player_pos = tilemap.player_starting_position;
+
+        let mut camera = Camera::new(
+            player_pos + Vec2::new(config.half_tile(), config.half_tile()),
+            config.screen_width,
+            config.screen_height,
         );
 
-        Self {
+        let mut animations = load_animations(&mut assets)?;
+
+        let atlas = match Atlas::load(&mut assets, ATLAS_PATH) {
+            Ok(atlas) => atlas,
+            Err(AssetError::Io { source, .. }) if source.kind() == ErrorKind::NotFound => {
+                let mut builder = AtlasBuilder::default();
+                builder.add_animations(&animations);
+                builder.add_tilemap(&tilemap);
+                builder.build()
+            }
+            Err(error) => return Err(error),
+        };
+        tilemap.use_atlas(&atlas);
+        animations.use_atlas(&atlas);
+        // The images everything was packed from are no longer used
+        assets.unload_unused();
+
+        fit_to_level(&mut camera, &tilemap);
+
+        Ok(Self {
+            config,
             camera,
             tilemap,
-            character_spritesheet: Default::default(),
-            effects_spritesheet: Default::default(),
+            load_options,
+            atlas,
+            animations,
+            animation_events: Default::default(),
+            render_queue: Default::default(),
             screen_transition: Default::default(),
-            light_map: Default::default(),
-        }
+            post_process: Default::default(),
+            light_map: LightMap::new(config.screen_width, config.screen_height),
+            game_flags: Default::default(),
+            pushable_state: Default::default(),
+            hot_reload: Default::default(),
+            assets,
+        })
     }
-}
 
-pub struct CharacterSpritesheet(pub Spritesheet);
-
-impl Default for CharacterSpritesheet {
-    fn default() -> Self {
-        CharacterSpritesheet(
-            Spritesheet::new(
-                "./assets/char.png",
-                SpritesheetConfig {
-                    padding: 1,
-                    ..Default::default()
-                },
-            )
-            .unwrap(),
-        )
+    /// Loads the world again the way [`Resources::new`] loaded it, staying in the current level.
+    /// If that level was removed, the new world starts in its first level and the error is
+    /// returned.
+    pub fn reload_world(&mut self) -> Result<(), AssetError> {
+        let mut tilemap = load_world(&self.config, &self.assets, self.load_options)?;
+        tilemap.use_atlas(&self.atlas);
+
+        let kept_level = tilemap.change_level(self.tilemap.current_level_id());
+        self.tilemap = tilemap;
+        fit_to_level(&mut self.camera, &self.tilemap);
+        Ok(kept_level?)
     }
 }
 
-pub struct EffectsSpritesheet(pub Spritesheet);
+/// Loads the world and checks that it fits the config
+fn load_world(
+    config: &EngineConfig,
+    assets: &Assets,
+    options: LoadOptions,
+) -> Result<TileMap, AssetError> {
+    let tilemap = assets.map(WORLD_PATH, options)?;
 
-impl Default for EffectsSpritesheet {
-    fn default() -> Self {
-        EffectsSpritesheet(Spritesheet::new("./assets/effects.png", Default::default()).unwrap())
+    // Movement steps a tile of the config at a time, so it has to be the grid of the levels
+    if tilemap.tilesize() != config.tile_size as i64 {
+        return Err(AssetError::Invalid {
+            path: assets.source().display_path(WORLD_PATH),
+            reason: format!(
+                "tiles are {}px, but the engine config has {}px tiles",
+                tilemap.tilesize(),
+                config.tile_size
+            ),
+        });
     }
+    Ok(tilemap)
+}
+
+/// Animation sets and state machines of the game's characters and effects
+pub fn load_animations(assets: &mut Assets) -> Result<AnimationLibrary, AssetError> {
+    let mut animations = AnimationLibrary::default();
+    animations.load(assets, PLAYER_ANIMATIONS_PATH)?;
+    animations.load(assets, EFFECT_ANIMATIONS_PATH)?;
+    animations.load_controller(assets, PLAYER_CONTROLLER_PATH)?;
+    Ok(animations)
 }
 
 pub struct LightMap {
@@ -67,11 +150,12 @@ pub struct LightMap {
     pub scale: u32,
 }
 
-impl Default for LightMap {
-    fn default() -> Self {
+impl LightMap {
+    /// Light map covering a screen of the given size
+    pub fn new(screen_width: u32, screen_height: u32) -> Self {
         let scale = 1;
-        let width = SCREEN_WIDTH / scale;
-        let height = SCREEN_HEIGHT / scale;
+        let width = screen_width / scale;
+        let height = screen_height / scale;
 
         Self {
             buffer: vec![0; (width * height * 4) as usize],
@@ -80,10 +164,100 @@ impl Default for LightMap {
             scale,
         }
     }
-}
 
-impl LightMap {
     pub fn clear(&mut self) {
         self.buffer.fill(0);
     }
 }
+
+/// Named on/off switches for world state such as solved puzzles
+#[derive(Debug, Default)]
+pub struct GameFlags(HashSet<String>);
+
+impl GameFlags {
+    pub fn set(&mut self, flag: &str, value: bool) {
+        if value {
+            self.0.insert(flag.to_string());
+        } else {
+            self.0.remove(flag);
+        }
+    }
+
+    pub fn is_set(&self, flag: &str) -> bool {
+        self.0.contains(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::assets::AssetSource;
+
+    /// Level_1, the player's house
+    const HOUSE: &str = "7ff1ab00-9b00-11ef-b4cf-5d2c476edacb";
+
+    fn game_resources(load_options: LoadOptions) -> Resources {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let assets = Assets::new(AssetSource::Directory(root));
+        Resources::new(EngineConfig::default(), assets, load_options).unwrap()
+    }
+
+    fn drawn_from_atlas(resources: &Resources) -> bool {
+        let pages = resources.atlas.pages();
+        resources
+            .tilemap
+            .current_level()
+            .tilesets
+            .values()
+            .all(|tileset| pages.iter().any(|page| page.path() == tileset.image.path()))
+    }
+
+    #[test]
+    fn reloaded_world_is_prepared_like_the_first_one() {
+        let lazy = LoadOptions {
+            lazy: true,
+            prefetch: false,
+        };
+        let mut resources = game_resources(lazy);
+
+        resources.reload_world().unwrap();
+
+        assert!(drawn_from_atlas(&resources));
+        // Still loaded lazily, so the house is not built until it is entered
+        assert_eq!(resources.tilemap.levels().count(), 1);
+    }
+
+    #[test]
+    fn reloaded_world_stays_in_the_current_level() {
+        let mut resources = game_resources(LoadOptions::default());
+        resources.tilemap.change_level(HOUSE).unwrap();
+
+        resources.reload_world().unwrap();
+
+        assert_eq!(resources.tilemap.current_level_id(), HOUSE);
+        assert!(drawn_from_atlas(&resources));
+    }
+
+    #[test]
+    fn reloaded_world_is_checked_against_the_config() {
+        let mut resources = game_resources(LoadOptions::default());
+        resources.config.tile_size = 8;
+
+        let result = resources.reload_world();
+
+        assert!(matches!(result, Err(AssetError::Invalid { .. })));
+    }
+
+    #[test]
+    fn changed_tileset_is_drawn_from_its_own_file_after_a_reload() {
+        let mut resources = game_resources(LoadOptions::default());
+        resources.atlas.remove_image("new_bark_town.png");
+
+        resources.reload_world().unwrap();
+
+        let tilesets = &resources.tilemap.current_level().tilesets;
+        assert!(tilesets.values().any(|tileset| tileset.image.path() == "new_bark_town.png"));
+    }
+}
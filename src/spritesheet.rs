@@ -1,17 +1,86 @@
-use std::{collections::HashMap, path::Path};
+use std::{collections::HashMap, sync::Arc};
 
-use image::{DynamicImage, GenericImageView};
+use serde::{Deserialize, Serialize};
 
-use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::{
+    assets::{Handle, Image},
+    blend::blend_pixel,
+};
 
 pub struct Spritesheet {
-    image: DynamicImage,
+    image: Handle<Image>,
     sprite_width: u32,
     sprite_height: u32,
     padding: u32,
-    sprite_cache: HashMap<(u32, u32), Vec<u8>>,
 }
 
+/// Rectangle of pixels in a spritesheet image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SpriteRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// How a sprite is drawn
+#[derive(Debug, Clone)]
+pub struct DrawParams {
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub tint: Option<Tint>,
+    /// Opacity of the whole sprite, multiplied with the alpha of each pixel
+    pub alpha: f32,
+    pub palette: Option<Arc<Palette>>,
+}
+
+impl Default for DrawParams {
+    fn default() -> Self {
+        Self {
+            flip_x: false,
+            flip_y: false,
+            tint: None,
+            alpha: 1.0,
+            palette: None,
+        }
+    }
+}
+
+/// Colour mixed into every pixel of a sprite, e.g. a white flash when it takes damage
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tint {
+    pub color: [u8; 3],
+    /// From 0 for the original colours to 1 for a solid silhouette of the tint colour
+    pub amount: f32,
+}
+
+impl Tint {
+    fn apply(&self, pixel: &mut [u8; 4]) {
+        let amount = self.amount.clamp(0.0, 1.0);
+        for (channel, tint) in pixel.iter_mut().zip(self.color) {
+            let mixed = *channel as f32 * (1.0 - amount) + tint as f32 * amount;
+            *channel = mixed.round() as u8;
+        }
+    }
+}
+
+/// Replaces colours of a sprite, so one spritesheet can be drawn as differently coloured variants
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Palette(HashMap<[u8; 3], [u8; 3]>);
+
+impl Palette {
+    pub fn new(swaps: impl IntoIterator<Item = ([u8; 3], [u8; 3])>) -> Self {
+        Self(swaps.into_iter().collect())
+    }
+
+    fn apply(&self, pixel: &mut [u8; 4]) {
+        if let Some(color) = self.0.get(&[pixel[0], pixel[1], pixel[2]]) {
+            pixel[..3].copy_from_slice(color);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SpritesheetConfig {
     pub sprite_width: u32,
     pub sprite_height: u32,
@@ -29,53 +98,41 @@ impl Default for SpritesheetConfig {
 }
 
 impl Spritesheet {
-    pub fn new<P: AsRef<Path>>(
-        path: P,
-        config: SpritesheetConfig,
-    ) -> Result<Self, image::ImageError> {
-        let image = image::open(path)?;
-        Ok(Self {
+    pub fn new(image: Handle<Image>, config: SpritesheetConfig) -> Self {
+        Self {
             image,
             sprite_width: config.sprite_width,
             sprite_height: config.sprite_height,
             padding: config.padding,
-            sprite_cache: HashMap::new(),
-        })
-    }
-
-    /// Gets the pixel data for a sprite at the given pixel coordinate in the spritesheet
-    fn get_sprite_at_px(&mut self, sprite_x: u32, sprite_y: u32) -> Option<&Vec<u8>> {
-        // Check if the sprite is within bounds
-        if sprite_x + self.sprite_width > self.image.width()
-            || sprite_y + self.sprite_height > self.image.height()
-        {
-            return None;
         }
+    }
 
-        if self.sprite_cache.contains_key(&(sprite_x, sprite_y)) {
-            return self.sprite_cache.get(&(sprite_x, sprite_y));
-        }
-
-        // Extract sprite pixels
-        let mut sprite_data =
-            Vec::with_capacity((self.sprite_width * self.sprite_height * 4) as usize);
-
-        for y in 0..self.sprite_height {
-            for x in 0..self.sprite_width {
-                let pixel = self.image.get_pixel(sprite_x + x, sprite_y + y);
-                sprite_data.extend_from_slice(&pixel.0);
-            }
-        }
+    pub fn image(&self) -> &Handle<Image> {
+        &self.image
+    }
 
-        self.sprite_cache.insert((sprite_x, sprite_y), sprite_data);
-        self.sprite_cache.get(&(sprite_x, sprite_y))
+    /// Region of the sprite in a grid cell, None if it doesn't fit in the image
+    pub fn cell(&self, column: u32, row: u32) -> Option<SpriteRegion> {
+        // Cells far outside the image come from animation files and must not overflow
+        let x = column.checked_mul(self.sprite_width.checked_add(self.padding)?)?;
+        let y = row.checked_mul(self.sprite_height.checked_add(self.padding)?)?;
+        self.region(SpriteRegion {
+            x,
+            y,
+            width: self.sprite_width,
+            height: self.sprite_height,
+        })
     }
 
-    /// Get sprite using grid coordinates
-    fn get_sprite(&mut self, row_index: u32, col_index: u32) -> Option<&Vec<u8>> {
-        let sprite_x = (row_index * self.sprite_height) + (row_index * self.padding);
-        let sprite_y = (col_index * self.sprite_width) + (col_index * self.padding);
-        self.get_sprite_at_px(sprite_x, sprite_y)
+    /// The region if it fits in the image
+    pub fn region(&self, region: SpriteRegion) -> Option<SpriteRegion> {
+        let fits =
+            region.x.checked_add(region.width).is_some_and(|right| right <= self.image.width())
+                && region
+                    .y
+                    .checked_add(region.height)
+                    .is_some_and(|bottom| bottom <= self.image.height());
+        fits.then_some(region)
     }
 
     /// Draw a sprite directly to a pixel buffer at the specified position
@@ -84,41 +141,163 @@ impl Spritesheet {
     /// * `sprite_x` - The x index of the sprite in the spritesheet
     /// * `sprite_y` - The y index of the sprite in the spritesheet
     /// * `target` - The pixel buffer to draw the sprite to
+    /// * `target_width` - The width of the pixel buffer in pixels
     /// * `dest_x` - The x pixel position in the buffer to draw the sprite to
     /// * `dest_y` - The y pixel position in the buffer to draw the sprite to
     pub fn draw_sprite_to_buffer(
-        &mut self,
+        &self,
         sprite_x: u32,
         sprite_y: u32,
         target: &mut [u8],
+        target_width: u32,
         dest_x: i32,
         dest_y: i32,
     ) {
-        let sprite_height = self.sprite_height;
-        let sprite_width = self.sprite_width;
-        let sprite_data = self.get_sprite(sprite_x, sprite_y).unwrap();
+        if let Some(region) = self.cell(sprite_x, sprite_y) {
+            self.draw_region_to_buffer(region, target, target_width, dest_x, dest_y);
+        }
+    }
+
+    /// Draw any region of the image, e.g. a frame of an Aseprite export, to a pixel buffer with
+    /// its top left corner at the specified position
+    pub fn draw_region_to_buffer(
+        &self,
+        region: SpriteRegion,
+        target: &mut [u8],
+        target_width: u32,
+        dest_x: i32,
+        dest_y: i32,
+    ) {
+        self.draw_region_with(
+            region,
+            target,
+            target_width,
+            dest_x,
+            dest_y,
+            &DrawParams::default(),
+        );
+    }
+
+    /// Draw a region of the image flipped, recoloured or blended as the params say
+    pub fn draw_region_with(
+        &self,
+        region: SpriteRegion,
+        target: &mut [u8],
+        target_width: u32,
+        dest_x: i32,
+        dest_y: i32,
+        params: &DrawParams,
+    ) {
+        if let Some(region) = self.region(region) {
+            blit(
+                &self.image,
+                region,
+                target,
+                target_width,
+                dest_x,
+                dest_y,
+                params,
+            );
+        }
+    }
+}
+
+/// Draws a region of an image to an RGBA pixel buffer of the given width, like the frame. Tiles and
+/// sprites are both drawn with this, whether they are read from their own image or from an atlas
+/// page.
+pub(crate) fn blit(
+    image: &Image,
+    region: SpriteRegion,
+    target: &mut [u8],
+    target_width: u32,
+    dest_x: i32,
+    dest_y: i32,
+    params: &DrawParams,
+) {
+    let fits = region.x.checked_add(region.width).is_some_and(|right| right <= image.width())
+        && region.y.checked_add(region.height).is_some_and(|bottom| bottom <= image.height());
+    if !fits || target_width == 0 {
+        return;
+    }
+    let target_height = (target.len() / 4) as i32 / target_width as i32;
+
+    for y in 0..region.height {
+        let target_y = dest_y + y as i32;
+        if target_y >= target_height || target_y < 0 {
+            continue;
+        }
 
-        for y in 0..sprite_height {
-            let target_y = dest_y + y as i32;
-            if target_y >= SCREEN_HEIGHT as i32 || target_y < 0 {
+        // TODO: different method for fully opaque sprites should not iterate over rows
+        for x in 0..region.width {
+            let target_x = dest_x + x as i32;
+            if target_x >= target_width as i32 || target_x < 0 {
                 continue;
             }
 
-            // TODO: different method for fully opaque sprites should not iterate over rows
-            for x in 0..sprite_width {
-                let target_x = dest_x + x as i32;
-                if target_x >= SCREEN_WIDTH as i32 || target_x < 0 {
-                    continue;
-                }
-
-                let sprite_idx = ((y * sprite_width + x) * 4) as usize;
-                // Skip fully transparent pixels
-                if sprite_data[sprite_idx + 3] > 0 {
-                    let target_idx = ((target_y * SCREEN_WIDTH as i32 + target_x) * 4) as usize;
-                    target[target_idx..target_idx + 4]
-                        .copy_from_slice(&sprite_data[sprite_idx..sprite_idx + 4]);
-                }
+            let source_x = if params.flip_x {
+                region.width - 1 - x
+            } else {
+                x
+            };
+            let source_y = if params.flip_y {
+                region.height - 1 - y
+            } else {
+                y
+            };
+            let mut pixel = image.get_pixel(region.x + source_x, region.y + source_y).0;
+            // Skip fully transparent pixels
+            if pixel[3] == 0 {
+                continue;
             }
+
+            if let Some(palette) = &params.palette {
+                palette.apply(&mut pixel);
+            }
+            if let Some(tint) = params.tint {
+                tint.apply(&mut pixel);
+            }
+
+            let target_idx = ((target_y * target_width as i32 + target_x) * 4) as usize;
+            blend_pixel(
+                &mut target[target_idx..target_idx + 4],
+                &pixel,
+                params.alpha,
+            );
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_swaps_colours_before_tint_mixes_in() {
+        let mut pixel = [10, 20, 30, 255];
+        Palette::new([([10, 20, 30], [200, 0, 100])]).apply(&mut pixel);
+        assert_eq!(pixel, [200, 0, 100, 255]);
+
+        Tint {
+            color: [0, 255, 0],
+            amount: 0.5,
+        }
+        .apply(&mut pixel);
+        assert_eq!(pixel, [100, 128, 50, 255]);
+    }
+
+    #[test]
+    fn regions_past_the_largest_coordinate_do_not_fit() {
+        let image = Handle::new("sheet.png", Image::new(32, 32));
+        let spritesheet = Spritesheet::new(image, SpritesheetConfig::default());
+
+        assert!(spritesheet.cell(1, 1).is_some());
+        assert_eq!(spritesheet.cell(u32::MAX, 0), None);
+        let region = SpriteRegion {
+            x: u32::MAX,
+            y: 0,
+            width: 16,
+            height: 16,
+        };
+        assert_eq!(spritesheet.region(region), None);
+    }
+}
@@ -1,9 +1,10 @@
 use crate::{
+    camera::{Camera, Follow},
     components::{Player, Position},
     input::Input,
     resource::Resources,
+    tile::TileMap,
     vec2::Vec2,
-    TILE_SIZE,
 };
 use hecs::With;
 use pixels::Pixels;
@@ -11,6 +12,14 @@ pub use std::time::Duration;
 
 use super::System;
 
+/// Keeps the view inside the level the followed position is in and the levels streamed next to it
+pub fn fit_to_level(camera: &mut Camera, tilemap: &TileMap) {
+    let (min, max) = tilemap.pixel_bounds(camera.position());
+    camera.set_bounds(min.into(), max.into());
+}
+
+/// Moves the camera towards what it follows, the player unless it is locked or focused on another
+/// entity, and advances its shakes and pans
 pub struct CameraFollowSystem;
 
 const DAMPING: f32 = 20.0;
@@ -24,12 +33,32 @@ impl System for CameraFollowSystem {
         _: &Input,
         delta_time: Duration,
     ) {
+        let config = &resources.config;
         let camera = &mut resources.camera;
-        let offset = Vec2::new((TILE_SIZE / 2) as f32, TILE_SIZE as f32 / 2.0);
-        let dead_zone = TILE_SIZE as f32 * 2.5;
+        fit_to_level(camera, &resources.tilemap);
+        let offset = Vec2::new(config.half_tile(), config.half_tile());
+        let dead_zone = config.tile_size as f32 * 2.5;
+
+        let player = || {
+            let mut query = world.query::<With<&Position, &Player>>();
+            let position = query.iter().next().map(|(_, position)| *position);
+            position
+        };
+        let followed = match camera.follow() {
+            Follow::Player => player(),
+            Follow::Entity(entity, _) => match world.get::<&Position>(entity) {
+                Ok(position) => Some(*position),
+                Err(_) => {
+                    // The focused entity is gone
+                    camera.unlock();
+                    player()
+                }
+            },
+            Follow::Locked => None,
+        };
 
-        for (_, position) in world.query_mut::<With<&Position, &Player>>() {
-            let target_pos = *position + offset;
+        if let Some(position) = followed {
+            let target_pos = position + offset;
             let current_pos = camera.position();
 
             // Calculate distance from camera to target
@@ -48,5 +77,26 @@ impl System for CameraFollowSystem {
 
             camera.set_position(current_pos + movement);
         }
+
+        camera.update_effects(delta_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, SMALL};
+
+    #[test]
+    fn level_smaller_than_the_screen_is_centred() {
+        let mut tilemap = test_fixtures::load(SMALL);
+        tilemap.change_level("house").unwrap();
+        let mut camera = Camera::new(Vec2::new(8.0, 40.0), 160, 144);
+
+        fit_to_level(&mut camera, &tilemap);
+
+        // The house is 3 by 3 tiles
+        assert_eq!(camera.view_position(), Vec2::new(24.0, 24.0));
+        assert_eq!(camera.world_to_screen(Vec2::ZERO), Vec2::new(56.0, 48.0));
     }
 }
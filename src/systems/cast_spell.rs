@@ -1,10 +1,12 @@
-use hecs::{With, World};
+use hecs::World;
 
 use crate::{
-    components::{FireSpell, Light, Movement, Position, SpellEffect, SpellEffectType},
+    components::{
+        AnimatedSprite, AnimationController, FireSpell, Light, Movement, Position, SpellEffect,
+    },
     movement_util::Direction,
+    resource::EFFECT_ANIMATIONS_PATH,
     vec2::Vec2,
-    TILE_SIZE,
 };
 
 use super::System;
@@ -15,26 +17,46 @@ impl System for CastSpellSystem {
     fn update(
         &self,
         world: &mut hecs::World,
-        _: &mut crate::resource::Resources,
+        resources: &mut crate::resource::Resources,
         _: &mut pixels::Pixels,
         input: &crate::input::Input,
         _: std::time::Duration,
     ) {
         let mut spells_to_cast = Vec::new();
-        for (_, (position, movement)) in
-            world.query::<With<(&Position, &Movement), &FireSpell>>().iter()
-        {
-            if input.space() {
-                spells_to_cast.push((*position, movement.direction));
+        for (entity, (position, movement, spell, controller)) in world.query_mut::<(
+            &Position,
+            &Movement,
+            &FireSpell,
+            Option<&mut AnimationController>,
+        )>() {
+            match controller {
+                // Animated casters release the spell on a frame of their cast animation
+                Some(controller) => {
+                    if input.space() {
+                        controller.params.casting = true;
+                    }
+                    if resources
+                        .animation_events
+                        .sent_by("spell_release")
+                        .any(|sender| sender == entity)
+                    {
+                        spells_to_cast.push((*position, movement.direction, spell.clip.clone()));
+                    }
+                }
+                None if input.space() => {
+                    spells_to_cast.push((*position, movement.direction, spell.clip.clone()))
+                }
+                None => {}
             }
         }
 
-        for (caster_position, caster_direction) in spells_to_cast {
+        for (caster_position, caster_direction, clip) in spells_to_cast {
             cast_spell(
                 world,
                 caster_position,
                 caster_direction,
-                SpellEffectType::Fireball,
+                &clip,
+                resources.config.tile_size,
             );
         }
     }
@@ -44,15 +66,19 @@ pub fn cast_spell(
     world: &mut World,
     caster_position: Position,
     caster_direction: Direction,
-    spell_type: SpellEffectType,
+    clip: &str,
+    tile_size: u32,
 ) {
     for i in 1..=4 {
         let offset =
-            caster_direction.to_vector() * (TILE_SIZE as f32 * i as f32) + Vec2::new(0.0, -4.0);
+            caster_direction.to_vector() * (tile_size as f32 * i as f32) + Vec2::new(0.0, -4.0);
         let effect_position = caster_position + offset;
 
         world.spawn((
-            SpellEffect::new(spell_type),
+            SpellEffect {
+                clip: clip.to_string(),
+            },
+            AnimatedSprite::new(EFFECT_ANIMATIONS_PATH, clip),
             Position::new(effect_position.x, effect_position.y),
             Light::new(60.0, 0.07, [1.0, 0.8, 0.7]),
         ));
@@ -3,46 +3,102 @@ use std::time::Duration;
 use pixels::Pixels;
 
 use crate::{
-    components::{AnimatedSprite, Movement},
+    components::{AnimatedSprite, AnimationController, Movement, MovementMode},
     input::Input,
     resource::Resources,
 };
 
 use super::System;
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationEvent {
+    pub entity: hecs::Entity,
+    pub name: String,
+}
+
+// Resource
+/// Events sent by animations during the current update, such as footsteps or the frame a spell is
+/// released on. Systems that react to them run after `CharacterAnimationSystem`.
+#[derive(Debug, Default)]
+pub struct AnimationEvents(Vec<AnimationEvent>);
+
+impl AnimationEvents {
+    pub fn iter(&self) -> impl Iterator<Item = &AnimationEvent> {
+        self.0.iter()
+    }
+
+    /// Entities that sent the event
+    pub fn sent_by<'a>(&'a self, name: &'a str) -> impl Iterator<Item = hecs::Entity> + 'a {
+        self.0.iter().filter(move |event| event.name == name).map(|event| event.entity)
+    }
+
+    fn send(&mut self, entity: hecs::Entity, names: &[String]) {
+        self.0.extend(names.iter().map(|name| AnimationEvent {
+            entity,
+            name: name.clone(),
+        }));
+    }
+}
+
+/// Moves characters through the states of their animation state machine and plays the clip of the
+/// current state
 pub struct CharacterAnimationSystem;
 
 impl System for CharacterAnimationSystem {
     fn update(
         &self,
         hecs_world: &mut hecs::World,
-        _: &mut Resources,
+        resources: &mut Resources,
         _: &mut Pixels,
         input: &Input,
         delta_time: Duration,
     ) {
-        for (_, (sprite, movement)) in hecs_world.query_mut::<(&mut AnimatedSprite, &Movement)>() {
-            // BUG: letting go of input should not stop animation immediately, let two frames play
-            if movement.is_moving || input.x() != 0 || input.y() != 0 {
-                sprite.frame_time += delta_time.as_secs_f32();
-                if sprite.frame_time >= animation_frame_duration(input) {
-                    let frames = sprite.get_sprite_frames(&movement.direction, true);
-                    sprite.current_animation_frame =
-                        (sprite.current_animation_frame + 1) % frames.len();
-                    sprite.frame_time -= animation_frame_duration(input);
-                }
+        let events = &mut resources.animation_events;
+        events.0.clear();
+
+        for (entity, (sprite, controller, movement)) in
+            hecs_world.query_mut::<(&mut AnimatedSprite, &mut AnimationController, &Movement)>()
+        {
+            let params = &mut controller.params;
+            params.moving = movement.is_moving || input.x() != 0 || input.y() != 0;
+            params.running = input.shift();
+            params.surfing = movement.mode == MovementMode::Surfing;
+            params.direction = movement.direction;
+
+            let Some(machine) = resources.animations.controller(&controller.machine) else {
+                continue;
+            };
+
+            let entered = machine.transition(controller);
+            let Some(state) = machine.state(&controller.state) else {
+                continue;
+            };
+
+            let clip_name = state.clip_name(movement.direction);
+            if entered {
+                sprite.restart(&clip_name);
+                events.send(entity, machine.events(&sprite.clip, 0));
             } else {
-                sprite.current_animation_frame = 0; // Reset to first frame when not moving
-                sprite.frame_time = 0.0;
+                // Turning keeps the state and swaps to the clip for the new direction
+                sprite.play(&clip_name);
             }
-        }
-    }
-}
 
-fn animation_frame_duration(input: &Input) -> f32 {
-    if input.shift() {
-        0.075
-    } else {
-        0.125
+            let Some(clip) =
+                resources.animations.get(&sprite.animations).and_then(|set| set.clip(&sprite.clip))
+            else {
+                continue;
+            };
+
+            clip.advance_with(
+                &mut sprite.playback,
+                delta_time.mul_f32(state.speed),
+                |playback| {
+                    controller.frames_played += 1;
+                    if !playback.finished {
+                        events.send(entity, machine.events(&sprite.clip, playback.frame));
+                    }
+                },
+            );
+        }
     }
 }
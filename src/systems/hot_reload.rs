@@ -0,0 +1,178 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    assets::AssetSource,
+    font::{draw_text, wrap_text, LINE_HEIGHT},
+    input::Input,
+    resource::{Resources, WORLD_PATH},
+};
+
+use super::System;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Resource
+#[derive(Default)]
+pub struct HotReloadState {
+    time_since_poll: Duration,
+    /// Modification times from the last poll, None until the first poll
+    modified: Option<HashMap<String, SystemTime>>,
+    /// Latest load error for each kind of asset, cleared when it loads again
+    errors: BTreeMap<&'static str, String>,
+}
+
+impl HotReloadState {
+    pub fn errors(&self) -> impl Iterator<Item = &String> {
+        self.errors.values()
+    }
+
+    /// Files that changed since the last poll
+    fn poll(&mut self, source: &AssetSource, extra: &[String]) -> Vec<String> {
+        let current = watched_files(source, extra);
+        let changed = match &self.modified {
+            Some(previous) => current
+                .iter()
+                .filter(|(path, modified)| previous.get(*path) != Some(modified))
+                .map(|(path, _)| path.clone())
+                .collect(),
+            None => Vec::new(),
+        };
+        self.modified = Some(current);
+        changed
+    }
+
+    fn record<E: std::fmt::Display>(&mut self, asset: &'static str, result: Result<(), E>) {
+        match result {
+            Ok(()) => {
+                self.errors.remove(asset);
+            }
+            Err(error) => {
+                let message = format!("Failed to reload {}: {}", asset, error);
+                eprintln!("{}", message);
+                self.errors.insert(asset, message);
+            }
+        }
+    }
+}
+
+/// The LDtk project, every PNG in the asset directory and the extra files, by path relative to
+/// the asset directory. Embedded assets never change, so nothing is watched for them.
+fn watched_files(source: &AssetSource, extra: &[String]) -> HashMap<String, SystemTime> {
+    let pngs = source
+        .directory()
+        .and_then(|directory| fs::read_dir(directory).ok())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".png"));
+
+    std::iter::once(WORLD_PATH.to_string())
+        .chain(pngs)
+        .chain(extra.iter().cloned())
+        .filter_map(|path| {
+            let modified = source.modified(&path)?;
+            Some((path, modified))
+        })
+        .collect()
+}
+
+/// Reloads the tile map and animations in place when their files change on disk. Only added with
+/// the debug feature.
+pub struct HotReloadSystem;
+
+impl System for HotReloadSystem {
+    fn update(
+        &self,
+        _: &mut hecs::World,
+        resources: &mut Resources,
+        _: &mut pixels::Pixels,
+        _: &Input,
+        delta_time: Duration,
+    ) {
+        let state = &mut resources.hot_reload;
+        state.time_since_poll += delta_time;
+        if state.time_since_poll < POLL_INTERVAL {
+            return;
+        }
+        state.time_since_poll = Duration::ZERO;
+
+        let animation_files = resources.animations.paths().cloned().collect::<Vec<_>>();
+        let changed = state.poll(resources.assets.source(), &animation_files);
+        if changed.is_empty() {
+            return;
+        }
+
+        let mut reload_animations = false;
+        let mut reload_tilemap = false;
+        for path in &changed {
+            // Cached copies would otherwise be handed out again
+            resources.assets.forget(path);
+            resources.atlas.remove_image(path);
+
+            if resources.animations.uses(path) {
+                reload_animations = true;
+            } else {
+                // The project itself or one of its tileset images
+                reload_tilemap = true;
+            }
+        }
+
+        if reload_animations {
+            let result = resources.animations.reload(&mut resources.assets);
+            resources.hot_reload.record("animations", result);
+        }
+
+        if reload_tilemap {
+            let result = resources.reload_world();
+            resources.hot_reload.record("world", result);
+        }
+    }
+}
+
+/// Draws hot reload errors on top of the game. Only added with the debug feature.
+pub struct HotReloadErrorRenderSystem;
+
+impl System for HotReloadErrorRenderSystem {
+    fn update(
+        &self,
+        _: &mut hecs::World,
+        resources: &mut Resources,
+        pixels: &mut pixels::Pixels,
+        _: &Input,
+        _: Duration,
+    ) {
+        let width = resources.config.screen_width;
+        let lines = resources
+            .hot_reload
+            .errors()
+            .flat_map(|error| wrap_text(error, width))
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            return;
+        }
+
+        let frame = pixels.frame_mut();
+
+        // Dark background behind the text so it is readable on any level
+        let background_height = lines.len() as u32 * LINE_HEIGHT + 1;
+        for pixel in frame.chunks_exact_mut(4).take((width * background_height) as usize) {
+            pixel.copy_from_slice(&[32, 0, 0, 255]);
+        }
+
+        for (index, line) in lines.iter().enumerate() {
+            draw_text(
+                frame,
+                width,
+                line,
+                1,
+                1 + (index as u32 * LINE_HEIGHT) as i64,
+                [255, 96, 96, 255],
+            );
+        }
+    }
+}
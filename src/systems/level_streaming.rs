@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use hecs::{With, Without, World};
+use pixels::Pixels;
+
+use super::{camera::fit_to_level, System};
+use crate::{
+    camera::Camera,
+    components::{Movement, Player, Position, Pushable},
+    input::Input,
+    resource::Resources,
+    tile::{TileMap, TileMapError},
+    vec2::Vec2,
+};
+
+/// Makes a streamed neighbour the current level once the player walks onto it. Positions are
+/// relative to the current level, so everything is shifted into the coordinates of the new level.
+pub struct LevelStreamingSystem;
+
+impl System for LevelStreamingSystem {
+    fn update(
+        &self,
+        world: &mut World,
+        resources: &mut Resources,
+        _: &mut Pixels,
+        _: &Input,
+        _: Duration,
+    ) {
+        let tile_size = resources.config.tile_size;
+        if let Err(error) = stream_levels(
+            world,
+            &mut resources.tilemap,
+            &mut resources.camera,
+            tile_size,
+        ) {
+            eprintln!("Could not stream into level: {}", error);
+        }
+    }
+}
+
+/// Changes to the neighbour the player stands in, if they left the current level
+fn stream_levels(
+    world: &mut World,
+    tilemap: &mut TileMap,
+    camera: &mut Camera,
+    tile_size: u32,
+) -> Result<(), TileMapError> {
+    let mut crossing = None;
+    for (_, position) in world.query::<With<&Position, &Player>>().iter() {
+        let tile = position.nearest_tile(tile_size);
+        if tilemap.current_level().tiles.contains(tile) {
+            continue;
+        }
+        crossing = tilemap.neighbour_at(tile);
+    }
+
+    let Some((level_id, offset)) = crossing else {
+        return Ok(());
+    };
+
+    tilemap.change_level(&level_id)?;
+
+    // Outdoor levels are placed on the tile grid, which the loader checks
+    let tile_size = tile_size as i64;
+    let offset_tiles = (offset.x / tile_size, offset.y / tile_size);
+    let offset = Vec2::from(offset);
+
+    // Pushables belong to their level and are respawned when it changes
+    for (_, (position, movement)) in
+        world.query_mut::<Without<(&mut Position, Option<&mut Movement>), &Pushable>>()
+    {
+        *position = *position - offset;
+
+        if let Some(target_tile) = movement.and_then(|movement| movement.forced_step.as_mut()) {
+            target_tile.0 -= offset_tiles.0;
+            target_tile.1 -= offset_tiles.1;
+        }
+    }
+
+    camera.move_origin(offset);
+    fit_to_level(camera, tilemap);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, SMALL};
+
+    const TILE_SIZE: u32 = 16;
+
+    #[test]
+    fn crossing_into_a_neighbour_moves_positions_and_steps_into_its_coordinates() {
+        let mut tilemap = test_fixtures::load(SMALL);
+        let mut camera = Camera::new(Vec2::new(64.0, 16.0), 160, 144);
+        let mut world = World::new();
+        let player = world.spawn((
+            Player,
+            Position::at_tile(4, 1, TILE_SIZE),
+            Movement {
+                forced_step: Some((5, 1)),
+                ..Movement::new(60.0)
+            },
+        ));
+        let follower = world.spawn((Position::new(56.0, 16.0),));
+
+        stream_levels(&mut world, &mut tilemap, &mut camera, TILE_SIZE).unwrap();
+
+        assert_eq!(tilemap.current_level().identifier, "East");
+        assert_eq!(
+            *world.get::<&Position>(player).unwrap(),
+            Position::at_tile(0, 1, TILE_SIZE)
+        );
+        assert_eq!(
+            world.get::<&Movement>(player).unwrap().forced_step,
+            Some((1, 1))
+        );
+        assert_eq!(
+            *world.get::<&Position>(follower).unwrap(),
+            Position::new(-8.0, 16.0)
+        );
+
+        // Walking back crosses the same border the other way
+        *world.get::<&mut Position>(player).unwrap() = Position::at_tile(-1, 1, TILE_SIZE);
+        world.get::<&mut Movement>(player).unwrap().forced_step = Some((-2, 1));
+        stream_levels(&mut world, &mut tilemap, &mut camera, TILE_SIZE).unwrap();
+
+        assert_eq!(tilemap.current_level().identifier, "Start");
+        assert_eq!(
+            *world.get::<&Position>(player).unwrap(),
+            Position::at_tile(3, 1, TILE_SIZE)
+        );
+        assert_eq!(
+            world.get::<&Movement>(player).unwrap().forced_step,
+            Some((2, 1))
+        );
+    }
+
+    #[test]
+    fn staying_inside_the_level_does_not_stream() {
+        let mut tilemap = test_fixtures::load(SMALL);
+        let mut camera = Camera::new(Vec2::new(48.0, 16.0), 160, 144);
+        let mut world = World::new();
+        world.spawn((Player, Position::at_tile(3, 1, TILE_SIZE)));
+
+        stream_levels(&mut world, &mut tilemap, &mut camera, TILE_SIZE).unwrap();
+
+        assert_eq!(tilemap.current_level().identifier, "Start");
+    }
+}
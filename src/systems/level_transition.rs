@@ -1,13 +1,18 @@
 use hecs::{With, World};
 
-use super::System;
+use super::{
+    camera::fit_to_level,
+    movement::can_enter,
+    post_process::{EffectId, PostEffect, PostProcessStack},
+    System,
+};
 use crate::{
-    components::{Movement, Player, Position},
+    components::{Inventory, Movement, Player, Position},
+    input::Input,
     movement_util::Direction,
-    resource::Resources,
-    tile::TileMap,
+    resource::{GameFlags, Resources},
+    tile::{Level, Lock, TileMap, Transition, TransitionStyle, TransitionTrigger},
     vec2::Vec2,
-    TILE_SIZE,
 };
 
 const FADE_SPEED: f32 = 5.0;
@@ -17,60 +22,51 @@ const FADE_UPDATE_INTERVAL: f32 = 1.0 / 15.0; // 15 FPS fade
 #[derive(Clone)]
 pub struct ScreenTransition {
     state: TransitionPhase,
+    style: TransitionStyle,
     fade_alpha: f32,
     time_since_last_fade: f32,
+    /// Post-process effect showing the fade while there is one
+    effect: Option<EffectId>,
 }
 
 #[derive(Clone)]
 enum TransitionPhase {
     None,
-    FadingOut {
-        destination_level_id: String,
-        destination_pos: Position,
-        direction: Direction,
-    },
+    OpeningDoor(Warp),
+    FadingOut(Warp),
     FadingIn,
 }
 
+#[derive(Clone)]
+struct Warp {
+    entrance_tile: (i64, i64),
+    destination_level_id: String,
+    destination_pos: Position,
+    direction: Direction,
+    walk_out: bool,
+}
+
 impl Default for ScreenTransition {
     fn default() -> Self {
         Self {
             state: TransitionPhase::None,
+            style: TransitionStyle::default(),
             fade_alpha: 0.0,
             time_since_last_fade: 0.0,
+            effect: None,
         }
     }
 }
 
 pub struct LevelTransitionSystem;
 
-impl LevelTransitionSystem {
-    fn apply_fade(pixels: &mut pixels::Pixels, fade_alpha: f32) {
-        let frame = pixels.frame_mut();
-        let alpha = (fade_alpha * 255.0) as u8;
-
-        for pixel in frame.chunks_exact_mut(4) {
-            // Convert everything to u16 to avoid overflow
-            let r = pixel[0] as u16;
-            let g = pixel[1] as u16;
-            let b = pixel[2] as u16;
-
-            // Calculate the blend with white (255) using linear interpolation
-            pixel[0] = (r + ((255 - r) * alpha as u16) / 255) as u8;
-            pixel[1] = (g + ((255 - g) * alpha as u16) / 255) as u8;
-            pixel[2] = (b + ((255 - b) * alpha as u16) / 255) as u8;
-            // Alpha channel (pixel[3]) remains unchanged
-        }
-    }
-}
-
 impl System for LevelTransitionSystem {
     fn update(
         &self,
         world: &mut World,
         resources: &mut Resources,
-        pixels: &mut pixels::Pixels,
-        _input: &crate::input::Input,
+        _: &mut pixels::Pixels,
+        input: &Input,
         delta_time: std::time::Duration,
     ) {
         let transition = &mut resources.screen_transition.clone();
@@ -80,39 +76,45 @@ impl System for LevelTransitionSystem {
 
         match transition.state.clone() {
             TransitionPhase::None => {
-                if let Some((destination_level_id, destination_pos, direction)) =
-                    detect_transition(world, &resources.tilemap)
-                {
-                    transition.state = TransitionPhase::FadingOut {
-                        destination_level_id,
-                        destination_pos,
-                        direction,
+                if let Some((entrance, warp)) = detect_transition(
+                    world,
+                    &resources.tilemap,
+                    input,
+                    &resources.game_flags,
+                    resources.config.tile_size,
+                ) {
+                    transition.style = entrance.style;
+                    transition.state = if entrance.door {
+                        TransitionPhase::OpeningDoor(warp)
+                    } else {
+                        TransitionPhase::FadingOut(warp)
                     };
                 }
             }
-            TransitionPhase::FadingOut {
-                destination_level_id,
-                destination_pos,
-                direction,
-            } => {
+            TransitionPhase::OpeningDoor(warp) => {
+                let door =
+                    resources.tilemap.current_level_mut().animation_at_mut(warp.entrance_tile);
+
+                match door {
+                    Some(animation) if animation.is_finished() => {
+                        transition.state = TransitionPhase::FadingOut(warp);
+                    }
+                    Some(animation) => {
+                        if !animation.is_playing() {
+                            animation.play_once();
+                        }
+                    }
+                    None => transition.state = TransitionPhase::FadingOut(warp),
+                }
+            }
+            TransitionPhase::FadingOut(warp) => {
                 if should_update_fade {
                     transition.fade_alpha += FADE_SPEED * FADE_UPDATE_INTERVAL;
                     transition.time_since_last_fade = 0.0;
 
                     if transition.fade_alpha >= 1.0 {
                         transition.fade_alpha = 1.0;
-
-                        // TODO: split up actual level change and visual transition stuff
-                        // to separate places
-                        resources.tilemap.change_level(&destination_level_id);
-                        for (_, (position, movement)) in
-                            world.query_mut::<With<(&mut Position, &mut Movement), &Player>>()
-                        {
-                            *position = destination_pos;
-                            movement.direction = direction;
-                            let offset = Vec2::new(TILE_SIZE as f32 / 2.0, TILE_SIZE as f32 / 2.0);
-                            resources.camera.set_position(*position + offset);
-                        }
+                        change_level(world, resources, &warp);
                         transition.state = TransitionPhase::FadingIn;
                     }
                 }
@@ -130,32 +132,193 @@ impl System for LevelTransitionSystem {
             }
         }
 
-        if transition.fade_alpha > 0.0 {
-            Self::apply_fade(pixels, resources.screen_transition.fade_alpha);
-        }
+        show_fade(transition, &mut resources.post_process);
 
         resources.screen_transition = transition.to_owned();
     }
 }
 
+/// Keeps an effect on the post-process stack at the strength of the fade while fading
+fn show_fade(transition: &mut ScreenTransition, post_process: &mut PostProcessStack) {
+    if transition.fade_alpha <= 0.0 {
+        if let Some(id) = transition.effect.take() {
+            post_process.remove(id);
+        }
+        return;
+    }
+
+    let id = match transition.effect {
+        Some(id) if post_process.contains(id) => id,
+        _ => {
+            let effect = match transition.style {
+                TransitionStyle::FadeWhite => PostEffect::Fade {
+                    color: [255, 255, 255],
+                },
+                TransitionStyle::FadeBlack => PostEffect::Fade { color: [0, 0, 0] },
+                TransitionStyle::Iris => PostEffect::Iris,
+            };
+            post_process.push(effect)
+        }
+    };
+    post_process.set_strength(id, transition.fade_alpha);
+    transition.effect = Some(id);
+}
+
+// TODO: split up actual level change and visual transition stuff to separate places
+fn change_level(world: &mut World, resources: &mut Resources, warp: &Warp) {
+    close_door(resources.tilemap.current_level_mut(), warp.entrance_tile);
+
+    if let Err(error) = resources.tilemap.change_level(&warp.destination_level_id) {
+        eprintln!("Could not enter level: {}", error);
+        return;
+    }
+
+    let tiles = &resources.tilemap.current_level().tiles;
+    let config = &resources.config;
+
+    for (_, (position, movement)) in
+        world.query_mut::<With<(&mut Position, &mut Movement), &Player>>()
+    {
+        *position = warp.destination_pos;
+        movement.direction = warp.direction;
+        let offset = Vec2::new(config.half_tile(), config.half_tile());
+        resources.camera.set_position(*position + offset);
+        fit_to_level(&mut resources.camera, &resources.tilemap);
+
+        if warp.walk_out {
+            let (tile_x, tile_y) = position.tile(config.tile_size);
+            let step_tile = (
+                tile_x + warp.direction.x() as i64,
+                tile_y + warp.direction.y() as i64,
+            );
+            if tiles.get(step_tile).is_some_and(|tile| can_enter(movement.mode, tile)) {
+                movement.forced_step = Some(step_tile);
+            }
+        }
+    }
+}
+
+/// Closes the door behind the player once it has opened, so it opens again next time
+fn close_door(level: &mut Level, entrance_tile: (i64, i64)) {
+    if let Some(animation) = level.animation_at_mut(entrance_tile) {
+        if !animation.is_playing() {
+            animation.pause();
+        }
+    }
+}
+
 fn detect_transition(
     world: &mut World,
     tilemap: &TileMap,
-) -> Option<(String, Position, Direction)> {
+    input: &Input,
+    game_flags: &GameFlags,
+    tile_size: u32,
+) -> Option<(Transition, Warp)> {
     let tiles = &tilemap.current_level().tiles;
 
-    for (_, position) in world.query_mut::<With<&mut Position, &Player>>() {
-        let tile = position.aligned_tile()?;
-        if let Some(transition) = &tiles[&(tile.0, tile.1)].transition {
-            if let Some(destination) = tilemap.entities.get(&transition.destination) {
-                return Some((
-                    destination.level_id.clone(),
-                    destination.position,
-                    destination.direction.expect("Destination entity should have a direction"),
-                ));
-            }
+    for (_, (position, movement, inventory)) in
+        world.query_mut::<With<(&mut Position, &Movement, Option<&Inventory>), &Player>>()
+    {
+        let tile = position.aligned_tile(tile_size)?;
+        let Some(transition) = tiles.get(tile).and_then(|tile| tile.transition.as_ref()) else {
+            continue;
+        };
+
+        let triggered = match transition.trigger {
+            TransitionTrigger::Step => true,
+            TransitionTrigger::Walk(direction) => input.current_direction() == Some(direction),
+        };
+
+        if !triggered || movement.forced_step.is_some() {
+            continue;
+        }
+
+        if !is_unlocked(transition.lock.as_ref(), game_flags, inventory) {
+            continue;
+        }
+
+        if let Some(destination) = tilemap.entities.get(&transition.destination) {
+            let warp = Warp {
+                entrance_tile: tile,
+                destination_level_id: destination.level_id.clone(),
+                destination_pos: destination.position,
+                // Destinations without a direction keep the player facing the way they walked in
+                direction: destination.direction.unwrap_or(movement.direction),
+                walk_out: transition.walk_out,
+            };
+            return Some((transition.clone(), warp));
         }
     }
 
     None
 }
+
+fn is_unlocked(lock: Option<&Lock>, game_flags: &GameFlags, inventory: Option<&Inventory>) -> bool {
+    match lock {
+        None => true,
+        Some(Lock::Flag(flag)) => game_flags.is_set(flag),
+        Some(Lock::Item(item)) => inventory.is_some_and(|inventory| inventory.contains(item)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_fixtures::{self, SMALL};
+
+    #[test]
+    fn flag_lock_opens_while_the_flag_is_set() {
+        let lock = Lock::Flag("plate_pressed".to_string());
+        let mut game_flags = GameFlags::default();
+        assert!(!is_unlocked(Some(&lock), &game_flags, None));
+
+        game_flags.set("plate_pressed", true);
+        assert!(is_unlocked(Some(&lock), &game_flags, None));
+    }
+
+    #[test]
+    fn item_lock_opens_when_the_item_is_carried() {
+        let lock = Lock::Item("key".to_string());
+        let game_flags = GameFlags::default();
+        let mut inventory = Inventory::default();
+        assert!(!is_unlocked(Some(&lock), &game_flags, None));
+        assert!(!is_unlocked(Some(&lock), &game_flags, Some(&inventory)));
+
+        inventory.add("key");
+        assert!(is_unlocked(Some(&lock), &game_flags, Some(&inventory)));
+        assert!(is_unlocked(None, &game_flags, None));
+    }
+
+    #[test]
+    fn opened_door_is_closed_when_leaving_the_level() {
+        let mut tilemap = test_fixtures::load(SMALL);
+        let level = tilemap.current_level_mut();
+        let door = level.animation_at_mut((3, 3)).unwrap();
+        door.play_once();
+        door.update(Duration::from_millis(200));
+        door.update(Duration::from_millis(100));
+        assert!(door.is_finished());
+
+        close_door(level, (3, 3));
+        tilemap.change_level("house").unwrap();
+        tilemap.change_level("start").unwrap();
+
+        let door = tilemap.current_level_mut().animation_at_mut((3, 3)).unwrap();
+        assert!(!door.is_playing());
+        assert!(!door.is_finished());
+        assert_eq!(door.frames()[0], door.current_position());
+    }
+
+    #[test]
+    fn door_that_is_still_opening_is_left_alone() {
+        let mut tilemap = test_fixtures::load(SMALL);
+        let level = tilemap.current_level_mut();
+        level.animation_at_mut((3, 3)).unwrap().play_once();
+
+        close_door(level, (3, 3));
+
+        assert!(level.animation_at_mut((3, 3)).unwrap().is_playing());
+    }
+}
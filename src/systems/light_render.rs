@@ -6,9 +6,9 @@ use super::System;
 use crate::{
     camera::Camera,
     components::{Light, Position},
+    config::EngineConfig,
     input::Input,
     resource::{LightMap, Resources},
-    SCREEN_HEIGHT, SCREEN_WIDTH, TILE_SIZE,
 };
 
 pub struct LightRenderSystem;
@@ -27,13 +27,19 @@ impl System for LightRenderSystem {
             return;
         }
 
-        render_lighting(pixels.frame_mut(), &resources.light_map);
+        render_lighting(pixels.frame_mut(), &resources.config, &resources.light_map);
     }
 }
 
-fn update_light(light: &Light, position: &Position, light_map: &mut LightMap, camera: &Camera) {
+fn update_light(
+    light: &Light,
+    position: &Position,
+    light_map: &mut LightMap,
+    camera: &Camera,
+    config: &EngineConfig,
+) {
     // Offsetting the position by half a tile size to center the light in the tile
-    let position_offset = Position::new(TILE_SIZE as f32 / 2.0, TILE_SIZE as f32 / 2.0);
+    let position_offset = Position::new(config.half_tile(), config.half_tile());
     let screen_pos = camera.world_to_screen(*position + position_offset);
     let scaled_x = (screen_pos.x / light_map.scale as f32) as i32;
     let scaled_y = (screen_pos.y / light_map.scale as f32) as i32;
@@ -95,20 +101,26 @@ impl System for LightUpdateSystem {
         resources.light_map.clear();
 
         for (_, (light, position)) in world.query::<(&Light, &Position)>().iter() {
-            update_light(light, position, &mut resources.light_map, &resources.camera);
+            update_light(
+                light,
+                position,
+                &mut resources.light_map,
+                &resources.camera,
+                &resources.config,
+            );
         }
     }
 }
 
-fn render_lighting(frame: &mut [u8], light_map: &LightMap) {
+fn render_lighting(frame: &mut [u8], config: &EngineConfig, light_map: &LightMap) {
     let ambient_light = [0.10, 0.10, 0.15]; // Lower R&G, higher B
 
-    for y in 0..SCREEN_HEIGHT {
-        for x in 0..SCREEN_WIDTH {
+    for y in 0..config.screen_height {
+        for x in 0..config.screen_width {
             let light_x = (x / light_map.scale) as usize;
             let light_y = (y / light_map.scale) as usize;
             let light_idx = (light_y * light_map.width as usize + light_x) * 4;
-            let frame_idx = ((y * SCREEN_WIDTH + x) * 4) as usize;
+            let frame_idx = ((y * config.screen_width + x) * 4) as usize;
 
             for i in 0..3 {
                 let light_level = light_map.buffer[light_idx + i] as f32 / 255.0;
@@ -8,12 +8,18 @@ pub mod camera;
 pub mod cast_spell;
 pub mod character_animation;
 pub mod debug_grid;
+pub mod hot_reload;
+pub mod level_streaming;
 pub mod level_transition;
 pub mod light_control;
 pub mod light_render;
 pub mod movement;
+pub mod post_process;
+pub mod pushable;
+pub mod render_queue;
 pub mod spell_effect;
 pub mod sprite_render;
+pub mod surf;
 pub mod tile_animation;
 pub mod tile_render;
 
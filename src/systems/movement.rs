@@ -1,18 +1,17 @@
-use std::time::Duration;
+use std::{collections::HashSet, time::Duration};
 
 use pixels::Pixels;
 
 use crate::{
-    components::{Movement, Position},
+    components::{Movement, MovementMode, Position, Pushable},
     input::Input,
     movement_util::{Direction, PositionExt},
     resource::Resources,
-    tile::TileMap,
+    tile::{Terrain, TileData, TileMap},
     vec2::Vec2,
-    TILE_SIZE,
 };
 
-use super::System;
+use super::{pushable::occupied_tiles, System};
 
 pub struct MovementSystem;
 
@@ -23,8 +22,11 @@ struct MovementContext<'a> {
     position: &'a mut Position,
     movement: &'a mut Movement,
     tilemap: &'a TileMap,
+    tile_size: u32,
     delta_time: Duration,
     input: &'a Input,
+    // Tiles blocked by other entities
+    occupied: &'a HashSet<(i64, i64)>,
 }
 
 impl System for MovementSystem {
@@ -36,13 +38,22 @@ impl System for MovementSystem {
         input: &Input,
         delta_time: Duration,
     ) {
-        for (_, (position, movement)) in world.query_mut::<(&mut Position, &mut Movement)>() {
+        let tile_size = resources.config.tile_size;
+        let occupied = occupied_tiles(world, tile_size);
+        let no_input = Input::new();
+
+        for (_, (position, movement, pushable)) in
+            world.query_mut::<(&mut Position, &mut Movement, Option<&Pushable>)>()
+        {
             let mut ctx = MovementContext {
                 position,
                 movement,
                 tilemap: &resources.tilemap,
+                tile_size,
                 delta_time,
-                input,
+                // Pushables only move when something pushes them
+                input: if pushable.is_some() { &no_input } else { input },
+                occupied: &occupied,
             };
             handle_movement(&mut ctx);
         }
@@ -50,11 +61,16 @@ impl System for MovementSystem {
 }
 
 fn handle_movement(ctx: &mut MovementContext) {
+    if let Some(target_tile) = ctx.movement.forced_step {
+        handle_forced_step(ctx, target_tile);
+        return;
+    }
+
     if ctx.movement.input_not_in_same_direction(ctx.input)
         && will_reach_next_tile_in_next_update(ctx)
     {
         ctx.movement.is_moving = false;
-        snap_to_grid(ctx.position);
+        snap_to_grid(ctx.position, ctx.tile_size);
 
         if ctx.input.none() {
             ctx.movement.start_delay = Duration::ZERO;
@@ -84,23 +100,111 @@ fn handle_movement(ctx: &mut MovementContext) {
         }
 
         // Changing directions
-        if is_on_grid(ctx.position) {
+        if is_on_grid(ctx.position, ctx.tile_size) {
             ctx.movement.direction = current_direction;
         }
     };
 
     if is_traversable(ctx) && ctx.movement.is_moving {
+        let previous_position = *ctx.position;
         apply_movement(ctx);
+
+        if let Some(tile) = arrived_tile(
+            previous_position,
+            *ctx.position,
+            ctx.movement.direction,
+            ctx.tile_size,
+        ) {
+            handle_tile_arrival(ctx, tile);
+        }
     } else {
         ctx.movement.is_moving = false;
         ctx.movement.idle_timer += ctx.delta_time;
-        snap_to_grid(ctx.position);
+        snap_to_grid(ctx.position, ctx.tile_size);
+    }
+}
+
+/// Moves towards the target tile without looking at input or collision, the target is expected
+/// to have been checked by whoever forced the step
+fn handle_forced_step(ctx: &mut MovementContext, target_tile: (i64, i64)) {
+    let target_position = Position::at_tile(target_tile.0, target_tile.1, ctx.tile_size);
+    let next_position = next_position(ctx);
+    let remaining_distance = (target_position - *ctx.position).length();
+
+    if (next_position - *ctx.position).length() >= remaining_distance {
+        *ctx.position = target_position;
+        ctx.movement.forced_step = None;
+        ctx.movement.is_moving = false;
+        handle_tile_arrival(ctx, target_tile);
+    } else {
+        ctx.movement.is_moving = true;
+        *ctx.position = next_position;
+    }
+}
+
+/// Applies terrain that overrides input, like ice and conveyors, once the entity is on a new tile
+fn handle_tile_arrival(ctx: &mut MovementContext, tile: (i64, i64)) {
+    let Some(tile_data) = ctx.tilemap.tile_at(tile) else {
+        return;
+    };
+
+    let direction = match tile_data.terrain {
+        Terrain::Ice => ctx.movement.direction,
+        Terrain::Conveyor(direction) => direction,
+        Terrain::Spinner(rotation) => ctx.movement.direction.rotated(rotation),
+        _ => return,
+    };
+
+    *ctx.position = Position::at_tile(tile.0, tile.1, ctx.tile_size);
+    ctx.movement.direction = direction;
+
+    let next_tile = (tile.0 + direction.x() as i64, tile.1 + direction.y() as i64);
+    let can_continue = !ctx.occupied.contains(&next_tile)
+        && ctx.tilemap.tile_at(next_tile).is_some_and(|next| can_enter(ctx.movement.mode, next));
+
+    if can_continue {
+        ctx.movement.forced_step = Some(next_tile);
+        ctx.movement.is_moving = true;
+    } else {
+        ctx.movement.is_moving = false;
+    }
+}
+
+/// Returns the tile whose grid aligned position was reached or passed when moving between the
+/// two positions
+fn arrived_tile(
+    from: Position,
+    to: Position,
+    direction: Direction,
+    tile_size: u32,
+) -> Option<(i64, i64)> {
+    let tile_size = tile_size as f32;
+    let (from_x, from_y, to_x, to_y) = match direction {
+        Direction::Right | Direction::Down => (
+            (from.x / tile_size).floor(),
+            (from.y / tile_size).floor(),
+            (to.x / tile_size).floor(),
+            (to.y / tile_size).floor(),
+        ),
+        Direction::Left | Direction::Up => (
+            (from.x / tile_size).ceil(),
+            (from.y / tile_size).ceil(),
+            (to.x / tile_size).ceil(),
+            (to.y / tile_size).ceil(),
+        ),
+    };
+
+    if from_x == to_x && from_y == to_y {
+        return None;
     }
+
+    Some((to_x as i64, to_y as i64))
 }
 
-fn snap_to_grid(position: &mut Position) {
-    position.x = (position.x / TILE_SIZE as f32).round() * TILE_SIZE as f32;
-    position.y = (position.y / TILE_SIZE as f32).round() * TILE_SIZE as f32;
+fn snap_to_grid(position: &mut Position, tile_size: u32) {
+    let tile_size = tile_size as f32;
+    position.x = (position.x / tile_size).round() * tile_size;
+    position.y = (position.y / tile_size).round() * tile_size;
 }
 
 fn will_reach_next_tile_in_next_update(ctx: &MovementContext) -> bool {
@@ -108,8 +212,8 @@ fn will_reach_next_tile_in_next_update(ctx: &MovementContext) -> bool {
         return false;
     }
 
-    let current_tile = ctx.position.tile_coordinate();
-    let next_tile = next_position(ctx).tile_coordinate();
+    let current_tile = ctx.position.tile_coordinate(ctx.tile_size);
+    let next_tile = next_position(ctx).tile_coordinate(ctx.tile_size);
     current_tile != next_tile
 }
 
@@ -117,26 +221,43 @@ fn apply_movement(ctx: &mut MovementContext) {
     *ctx.position = next_position(ctx);
 }
 
-fn is_on_grid(position: &Vec2) -> bool {
-    (position.y % TILE_SIZE as f32 == 0.0) && (position.x % TILE_SIZE as f32 == 0.0)
+fn is_on_grid(position: &Vec2, tile_size: u32) -> bool {
+    let tile_size = tile_size as f32;
+    (position.y % tile_size == 0.0) && (position.x % tile_size == 0.0)
 }
 
 /// Check if the tile at the leading edge of movement will be traversable after the next update.
 /// For right/down movement, checks the far edge of the sprite since position represents the top-left corner.
 fn is_traversable(ctx: &MovementContext) -> bool {
     let next_position = next_position(ctx);
+    let tile_size = ctx.tile_size as f32;
     let collision_pos = match ctx.movement.direction {
-        Direction::Right => Vec2::new(next_position.x + TILE_SIZE as f32, next_position.y),
-        Direction::Down => Vec2::new(next_position.x, next_position.y + TILE_SIZE as f32),
+        Direction::Right => Vec2::new(next_position.x + tile_size, next_position.y),
+        Direction::Down => Vec2::new(next_position.x, next_position.y + tile_size),
         _ => next_position,
     };
-    let collision_tile = collision_pos.tile_coordinate();
+    let collision_tile = collision_pos.tile_coordinate(ctx.tile_size);
+    if ctx.occupied.contains(&collision_tile) {
+        return false;
+    }
 
-    ctx.tilemap.current_level().tiles[&(collision_tile.0, collision_tile.1)].traversable
+    ctx.tilemap.tile_at(collision_tile).is_some_and(|tile| can_enter(ctx.movement.mode, tile))
+}
+
+/// Surfing entities can move across water and back onto land, but walking ones are kept on land
+pub(crate) fn can_enter(mode: MovementMode, tile: &TileData) -> bool {
+    match mode {
+        MovementMode::Walking => tile.traversable,
+        MovementMode::Surfing => tile.traversable || tile.terrain == Terrain::Water,
+    }
 }
 
 fn next_position(ctx: &MovementContext) -> Vec2 {
-    let movement_speed_multiplier = if ctx.input.shift() { 2.5 } else { 1.5 };
+    let movement_speed_multiplier = match ctx.movement.mode {
+        MovementMode::Walking if ctx.input.shift() => 2.5,
+        MovementMode::Walking => 1.5,
+        MovementMode::Surfing => 2.0,
+    };
     let movement_vector = ctx.movement.direction.to_vector();
     let movement_step = movement_vector
         * ctx.movement.speed
@@ -144,3 +265,223 @@ fn next_position(ctx: &MovementContext) -> Vec2 {
         * movement_speed_multiplier;
     *(ctx.position) + movement_step
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ivec2::IVec2,
+        test_fixtures::{self, TERRAIN_TILED},
+    };
+
+    #[test]
+    fn test_arrived_tile_moving_right() {
+        let tile = arrived_tile(
+            Vec2::new(30.0, 16.0),
+            Vec2::new(33.0, 16.0),
+            Direction::Right,
+            16,
+        );
+        assert_eq!(tile, Some((2, 1)));
+    }
+
+    #[test]
+    fn test_arrived_tile_moving_left_from_aligned_position() {
+        let tile = arrived_tile(
+            Vec2::new(32.0, 16.0),
+            Vec2::new(30.0, 16.0),
+            Direction::Left,
+            16,
+        );
+        assert_eq!(tile, None);
+    }
+
+    #[test]
+    fn test_arrived_tile_moving_up() {
+        let tile = arrived_tile(
+            Vec2::new(16.0, 18.0),
+            Vec2::new(16.0, 15.0),
+            Direction::Up,
+            16,
+        );
+        assert_eq!(tile, Some((1, 1)));
+    }
+
+    #[test]
+    fn test_arrived_tile_within_same_tile() {
+        let tile = arrived_tile(
+            Vec2::new(17.0, 16.0),
+            Vec2::new(20.0, 16.0),
+            Direction::Right,
+            16,
+        );
+        assert_eq!(tile, None);
+    }
+
+    #[test]
+    fn test_arrived_tile_with_smaller_tiles() {
+        let tile = arrived_tile(
+            Vec2::new(14.0, 8.0),
+            Vec2::new(17.0, 8.0),
+            Direction::Right,
+            8,
+        );
+        assert_eq!(tile, Some((2, 1)));
+    }
+
+    fn tile(traversable: bool, terrain: Terrain) -> TileData {
+        TileData {
+            position: IVec2::new(0, 0),
+            traversable,
+            terrain,
+            transition: None,
+        }
+    }
+
+    #[test]
+    fn test_walking_stays_on_land() {
+        assert!(can_enter(
+            MovementMode::Walking,
+            &tile(true, Terrain::Ground)
+        ));
+        assert!(!can_enter(
+            MovementMode::Walking,
+            &tile(false, Terrain::Water)
+        ));
+        assert!(!can_enter(
+            MovementMode::Walking,
+            &tile(false, Terrain::Wall)
+        ));
+    }
+
+    #[test]
+    fn test_surfing_crosses_water_and_returns_to_land() {
+        assert!(can_enter(
+            MovementMode::Surfing,
+            &tile(false, Terrain::Water)
+        ));
+        assert!(can_enter(
+            MovementMode::Surfing,
+            &tile(true, Terrain::Ground)
+        ));
+        assert!(!can_enter(
+            MovementMode::Surfing,
+            &tile(false, Terrain::Wall)
+        ));
+    }
+
+    const TILE_SIZE: u32 = 16;
+    const FRAME: Duration = Duration::from_millis(16);
+
+    /// An entity on the terrain fixture, moved a frame at a time like the system does
+    struct Walker {
+        tilemap: TileMap,
+        position: Position,
+        movement: Movement,
+    }
+
+    impl Walker {
+        fn at(tile: (i64, i64)) -> Self {
+            Self {
+                tilemap: test_fixtures::load(TERRAIN_TILED),
+                position: Position::at_tile(tile.0, tile.1, TILE_SIZE),
+                movement: Movement::new(48.0),
+            }
+        }
+
+        fn step(&mut self, input: &Input) {
+            let occupied = HashSet::new();
+            handle_movement(&mut MovementContext {
+                position: &mut self.position,
+                movement: &mut self.movement,
+                tilemap: &self.tilemap,
+                tile_size: TILE_SIZE,
+                delta_time: FRAME,
+                input,
+                occupied: &occupied,
+            });
+        }
+
+        /// Walks in the direction until the terrain takes over
+        fn walk_onto_terrain(&mut self, direction: Direction) {
+            let input = Input::holding(direction);
+            for _ in 0..100 {
+                self.step(&input);
+                if self.movement.forced_step.is_some() {
+                    return;
+                }
+            }
+            panic!("never reached terrain that moves the entity");
+        }
+
+        /// Lets the terrain move the entity until it stops, without any input
+        fn ride(&mut self) {
+            let input = Input::new();
+            for _ in 0..100 {
+                if self.movement.forced_step.is_none() {
+                    return;
+                }
+                self.step(&input);
+            }
+            panic!("terrain never stopped moving the entity");
+        }
+    }
+
+    #[test]
+    fn test_sliding_on_ice_stops_in_front_of_a_wall() {
+        let mut walker = Walker::at((0, 0));
+
+        walker.walk_onto_terrain(Direction::Right);
+        walker.ride();
+
+        assert_eq!(walker.position, Position::at_tile(3, 0, TILE_SIZE));
+        assert!(!walker.movement.is_moving);
+    }
+
+    #[test]
+    fn test_sliding_on_ice_stops_on_the_first_tile_off_the_ice() {
+        let mut walker = Walker::at((0, 1));
+
+        walker.walk_onto_terrain(Direction::Right);
+        walker.ride();
+
+        assert_eq!(walker.position, Position::at_tile(3, 1, TILE_SIZE));
+    }
+
+    #[test]
+    fn test_input_is_ignored_while_sliding() {
+        let mut walker = Walker::at((0, 0));
+        walker.walk_onto_terrain(Direction::Right);
+
+        let down = Input::holding(Direction::Down);
+        while walker.movement.forced_step.is_some() {
+            walker.step(&down);
+            assert_eq!(walker.position.y, 0.0);
+            assert_eq!(walker.movement.direction, Direction::Right);
+        }
+
+        assert_eq!(walker.position, Position::at_tile(3, 0, TILE_SIZE));
+    }
+
+    #[test]
+    fn test_conveyor_pushes_in_its_direction() {
+        let mut walker = Walker::at((0, 2));
+
+        walker.walk_onto_terrain(Direction::Right);
+        walker.ride();
+
+        assert_eq!(walker.position, Position::at_tile(1, 3, TILE_SIZE));
+        assert_eq!(walker.movement.direction, Direction::Down);
+    }
+
+    #[test]
+    fn test_spinner_turns_and_sends_off() {
+        let mut walker = Walker::at((2, 3));
+
+        walker.walk_onto_terrain(Direction::Right);
+        walker.ride();
+
+        assert_eq!(walker.position, Position::at_tile(3, 4, TILE_SIZE));
+        assert_eq!(walker.movement.direction, Direction::Down);
+    }
+}
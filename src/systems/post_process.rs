@@ -0,0 +1,340 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use pixels::Pixels;
+
+use crate::{input::Input, resource::Resources, vec2::Vec2};
+
+use super::System;
+
+/// Shades of the original Game Boy screen, from darkest to lightest
+pub const GAME_BOY_PALETTE: [[u8; 3]; 4] =
+    [[15, 56, 15], [48, 98, 48], [139, 172, 15], [155, 188, 15]];
+
+/// Changes the whole finished frame. Every effect is applied with a strength from 0, leaving the
+/// frame as it is, to 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostEffect {
+    /// Mixes every pixel towards the colour
+    Fade { color: [u8; 3] },
+    /// Adds the colour to every pixel, brightening the screen
+    Flash { color: [u8; 3] },
+    /// Removes colour, keeping brightness
+    Grayscale,
+    /// Reduces the screen to four shades by brightness, darkest first
+    Palette { shades: [[u8; 3]; 4] },
+    /// Darkens every other row like a CRT screen, by up to the darkness
+    Scanlines { darkness: f32 },
+    /// Shifts rows sideways in a sine wave, like heat haze or a dream sequence
+    Wavy {
+        /// Largest shift in pixels
+        amplitude: f32,
+        /// Rows per wave
+        wavelength: f32,
+        /// Waves per second
+        speed: f32,
+    },
+    /// Blacks out everything outside a circle in the middle of the screen, which closes as the
+    /// strength goes up
+    Iris,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EffectId(u64);
+
+#[derive(Debug)]
+struct ActiveEffect {
+    id: EffectId,
+    effect: PostEffect,
+    from: f32,
+    to: f32,
+    duration: Duration,
+    elapsed: Duration,
+    /// Removed when the duration is over instead of staying at the final strength
+    expires: bool,
+}
+
+impl ActiveEffect {
+    fn strength(&self) -> f32 {
+        if self.duration.is_zero() {
+            return self.to;
+        }
+        let progress = (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0);
+        self.from + (self.to - self.from) * progress
+    }
+
+    fn is_over(&self) -> bool {
+        self.expires && self.elapsed >= self.duration
+    }
+}
+
+// Resource
+/// Effects applied to the finished frame from bottom to top, pushed by scenes, cutscenes and
+/// transitions
+#[derive(Debug, Default)]
+pub struct PostProcessStack {
+    effects: Vec<ActiveEffect>,
+    next_id: u64,
+    /// Time since the stack was created, which moving effects like `Wavy` animate with
+    time: Duration,
+}
+
+impl PostProcessStack {
+    /// Applies the effect at full strength until it is removed
+    pub fn push(&mut self, effect: PostEffect) -> EffectId {
+        self.add(effect, 1.0, 1.0, Duration::ZERO, false)
+    }
+
+    /// Applies the effect at full strength for the duration
+    pub fn push_for(&mut self, effect: PostEffect, duration: Duration) -> EffectId {
+        self.add(effect, 1.0, 1.0, duration, true)
+    }
+
+    /// Moves the strength of the effect from one value to another over the duration, then keeps
+    /// it there, e.g. to fade out and stay black. Ramps down to 0 are removed when they end.
+    pub fn push_ramp(
+        &mut self,
+        effect: PostEffect,
+        from: f32,
+        to: f32,
+        duration: Duration,
+    ) -> EffectId {
+        self.add(effect, from, to, duration, to <= 0.0)
+    }
+
+    /// Flashes the screen with the colour, fading over the duration
+    pub fn flash(&mut self, color: [u8; 3], duration: Duration) -> EffectId {
+        self.push_ramp(PostEffect::Flash { color }, 1.0, 0.0, duration)
+    }
+
+    /// Removes the effect pushed last
+    pub fn pop(&mut self) -> Option<PostEffect> {
+        self.effects.pop().map(|active| active.effect)
+    }
+
+    pub fn remove(&mut self, id: EffectId) {
+        self.effects.retain(|active| active.id != id);
+    }
+
+    pub fn contains(&self, id: EffectId) -> bool {
+        self.effects.iter().any(|active| active.id == id)
+    }
+
+    /// Changes the strength of an effect, stopping any ramp it was on
+    pub fn set_strength(&mut self, id: EffectId, strength: f32) {
+        if let Some(active) = self.effects.iter_mut().find(|active| active.id == id) {
+            active.from = strength;
+            active.to = strength;
+        }
+    }
+
+    pub fn strength(&self, id: EffectId) -> Option<f32> {
+        self.effects.iter().find(|active| active.id == id).map(ActiveEffect::strength)
+    }
+
+    /// Changes the parameters of an effect
+    pub fn get_mut(&mut self, id: EffectId) -> Option<&mut PostEffect> {
+        let active = self.effects.iter_mut().find(|active| active.id == id)?;
+        Some(&mut active.effect)
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+
+    /// Advances ramps and removes effects whose time is over
+    pub fn update(&mut self, delta_time: Duration) {
+        self.time += delta_time;
+        for active in &mut self.effects {
+            active.elapsed += delta_time;
+        }
+        self.effects.retain(|active| !active.is_over());
+    }
+
+    /// Applies the effects to a frame of the given width
+    pub fn apply(&self, frame: &mut [u8], frame_width: u32) {
+        for active in &self.effects {
+            let strength = active.strength().clamp(0.0, 1.0);
+            if strength > 0.0 {
+                apply_effect(frame, frame_width, &active.effect, strength, self.time);
+            }
+        }
+    }
+
+    fn add(
+        &mut self,
+        effect: PostEffect,
+        from: f32,
+        to: f32,
+        duration: Duration,
+        expires: bool,
+    ) -> EffectId {
+        let id = EffectId(self.next_id);
+        self.next_id += 1;
+        self.effects.push(ActiveEffect {
+            id,
+            effect,
+            from,
+            to,
+            duration,
+            elapsed: Duration::ZERO,
+            expires,
+        });
+        id
+    }
+}
+
+fn apply_effect(
+    frame: &mut [u8],
+    frame_width: u32,
+    effect: &PostEffect,
+    strength: f32,
+    time: Duration,
+) {
+    let row_bytes = frame_width as usize * 4;
+    match effect {
+        PostEffect::Fade { color } => {
+            for pixel in frame.chunks_exact_mut(4) {
+                for (channel, target) in pixel.iter_mut().zip(color) {
+                    *channel = mix(*channel, *target, strength);
+                }
+            }
+        }
+        PostEffect::Flash { color } => {
+            for pixel in frame.chunks_exact_mut(4) {
+                for (channel, added) in pixel.iter_mut().zip(color) {
+                    *channel = channel.saturating_add((*added as f32 * strength) as u8);
+                }
+            }
+        }
+        PostEffect::Grayscale => {
+            for pixel in frame.chunks_exact_mut(4) {
+                let gray = luminance(pixel);
+                for channel in pixel.iter_mut().take(3) {
+                    *channel = mix(*channel, gray, strength);
+                }
+            }
+        }
+        PostEffect::Palette { shades } => {
+            for pixel in frame.chunks_exact_mut(4) {
+                let shade = shades[(luminance(pixel) as usize * shades.len() / 256).min(3)];
+                for (channel, target) in pixel.iter_mut().zip(shade) {
+                    *channel = mix(*channel, target, strength);
+                }
+            }
+        }
+        PostEffect::Scanlines { darkness } => {
+            let scale = 1.0 - darkness.clamp(0.0, 1.0) * strength;
+            for row in frame.chunks_exact_mut(row_bytes).skip(1).step_by(2) {
+                for pixel in row.chunks_exact_mut(4) {
+                    for channel in pixel.iter_mut().take(3) {
+                        *channel = (*channel as f32 * scale) as u8;
+                    }
+                }
+            }
+        }
+        PostEffect::Wavy {
+            amplitude,
+            wavelength,
+            speed,
+        } => {
+            let width = frame_width as i32;
+            let phase = time.as_secs_f32() * speed * TAU;
+            let mut original = vec![0; row_bytes];
+            for (y, row) in frame.chunks_exact_mut(row_bytes).enumerate() {
+                let wave = (y as f32 / wavelength.max(1.0) * TAU + phase).sin();
+                let shift = (wave * amplitude * strength).round() as i32;
+                if shift == 0 {
+                    continue;
+                }
+
+                // Pixels shifted in from outside the screen repeat the edge
+                original.copy_from_slice(row);
+                for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                    let source = (x as i32 - shift).clamp(0, width - 1) as usize * 4;
+                    pixel.copy_from_slice(&original[source..source + 4]);
+                }
+            }
+        }
+        PostEffect::Iris => {
+            let frame_height = frame.len() / row_bytes;
+            let center = Vec2::new(frame_width as f32 / 2.0, frame_height as f32 / 2.0);
+            let radius = center.length() * (1.0 - strength);
+
+            for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
+                let x = (i as u32 % frame_width) as f32;
+                let y = (i as u32 / frame_width) as f32;
+                if (Vec2::new(x, y) - center).length() > radius {
+                    pixel[0..3].fill(0);
+                }
+            }
+        }
+    }
+}
+
+fn mix(from: u8, to: u8, amount: f32) -> u8 {
+    (from as f32 + (to as f32 - from as f32) * amount).round() as u8
+}
+
+fn luminance(pixel: &[u8]) -> u8 {
+    (0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32).round() as u8
+}
+
+/// Applies the post-process stack to the finished frame. Registered after every system that draws
+/// to the frame.
+pub struct PostProcessSystem;
+
+impl System for PostProcessSystem {
+    fn update(
+        &self,
+        _: &mut hecs::World,
+        resources: &mut Resources,
+        pixels: &mut Pixels,
+        _: &Input,
+        delta_time: Duration,
+    ) {
+        resources.post_process.update(delta_time);
+        resources.post_process.apply(pixels.frame_mut(), resources.config.screen_width);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramps_hold_their_final_strength_unless_it_is_zero() {
+        let mut stack = PostProcessStack::default();
+        let fade = stack.push_ramp(
+            PostEffect::Fade { color: [0, 0, 0] },
+            0.0,
+            1.0,
+            Duration::from_millis(200),
+        );
+        let flash = stack.flash([255, 255, 255], Duration::from_millis(200));
+
+        stack.update(Duration::from_millis(100));
+        assert_eq!(stack.strength(fade), Some(0.5));
+        assert_eq!(stack.strength(flash), Some(0.5));
+
+        stack.update(Duration::from_millis(200));
+        assert_eq!(stack.strength(fade), Some(1.0));
+        assert!(!stack.contains(flash));
+    }
+
+    #[test]
+    fn palette_maps_brightness_to_shades() {
+        let mut frame = [0, 0, 0, 255, 255, 255, 255, 255, 128, 128, 128, 255];
+        apply_effect(
+            &mut frame,
+            3,
+            &PostEffect::Palette {
+                shades: GAME_BOY_PALETTE,
+            },
+            1.0,
+            Duration::ZERO,
+        );
+        assert_eq!(frame[0..3], GAME_BOY_PALETTE[0]);
+        assert_eq!(frame[4..7], GAME_BOY_PALETTE[3]);
+        assert_eq!(frame[8..11], GAME_BOY_PALETTE[2]);
+    }
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use hecs::{With, World};
+use pixels::Pixels;
+
+use super::{
+    movement::can_enter,
+    render_queue::RenderLayer,
+    tile_render::{tile_command, TileDrawParams},
+    System,
+};
+use crate::{
+    components::{Movement, MovementMode, Player, Position, Pushable},
+    input::Input,
+    movement_util::Direction,
+    resource::{GameFlags, Resources},
+    tile::TileMap,
+};
+
+const PUSHABLE_SPEED: f32 = 48.0;
+
+// Resource
+#[derive(Default)]
+pub struct PushableState {
+    spawned_level_id: Option<String>,
+    // Tile each pushable was left on, so puzzles keep their state when coming back to a level
+    positions: HashMap<String, (i64, i64)>,
+}
+
+/// Tiles taken up by pushables, including the tile they are being pushed onto
+pub fn occupied_tiles(world: &World, tile_size: u32) -> HashSet<(i64, i64)> {
+    let mut occupied = HashSet::new();
+    for (_, (position, movement)) in world.query::<With<(&Position, &Movement), &Pushable>>().iter()
+    {
+        occupied.insert(position.tile(tile_size));
+        if let Some(target_tile) = movement.forced_step {
+            occupied.insert(target_tile);
+        }
+    }
+    occupied
+}
+
+/// Spawns the pushables of the current level whenever the level changes
+pub struct PushableSpawnSystem;
+
+impl System for PushableSpawnSystem {
+    fn update(
+        &self,
+        world: &mut World,
+        resources: &mut Resources,
+        _: &mut Pixels,
+        _: &Input,
+        _: Duration,
+    ) {
+        let tile_size = resources.config.tile_size;
+        spawn_pushables(
+            world,
+            &mut resources.pushable_state,
+            &resources.tilemap,
+            tile_size,
+        );
+    }
+}
+
+/// Starts sliding a pushable when the player walks into it
+pub struct PushSystem;
+
+impl System for PushSystem {
+    fn update(
+        &self,
+        world: &mut World,
+        resources: &mut Resources,
+        _: &mut Pixels,
+        input: &Input,
+        _: Duration,
+    ) {
+        let Some(direction) = input.current_direction() else {
+            return;
+        };
+
+        push(
+            world,
+            &resources.tilemap,
+            direction,
+            resources.config.tile_size,
+        );
+    }
+}
+
+/// Sets the game flag of each pressure plate in the current level while a pushable rests on it
+pub struct PressurePlateSystem;
+
+impl System for PressurePlateSystem {
+    fn update(
+        &self,
+        world: &mut World,
+        resources: &mut Resources,
+        _: &mut Pixels,
+        _: &Input,
+        _: Duration,
+    ) {
+        update_pressure_plates(
+            world,
+            &resources.tilemap,
+            &mut resources.game_flags,
+            resources.config.tile_size,
+        );
+    }
+}
+
+/// Swaps the pushables in the world for those of the current level, remembering the tile each
+/// one was left on
+fn spawn_pushables(
+    world: &mut World,
+    state: &mut PushableState,
+    tilemap: &TileMap,
+    tile_size: u32,
+) {
+    let current_level_id = tilemap.current_level_id();
+    if state.spawned_level_id.as_deref() == Some(current_level_id) {
+        return;
+    }
+
+    let mut to_despawn = Vec::new();
+    for (entity, (pushable, position)) in world.query::<(&Pushable, &Position)>().iter() {
+        state.positions.insert(pushable.entity_id.clone(), position.nearest_tile(tile_size));
+        to_despawn.push(entity);
+    }
+
+    for entity in to_despawn {
+        let _ = world.despawn(entity);
+    }
+
+    for (entity_id, entity) in &tilemap.entities {
+        if entity.identifier != "Boulder" || entity.level_id != current_level_id {
+            continue;
+        }
+
+        let (tile_x, tile_y) =
+            state.positions.get(entity_id).copied().unwrap_or(entity.position.tile(tile_size));
+
+        world.spawn((
+            Pushable {
+                entity_id: entity_id.clone(),
+            },
+            Position::at_tile(tile_x, tile_y, tile_size),
+            Movement::new(PUSHABLE_SPEED),
+        ));
+    }
+
+    state.spawned_level_id = Some(current_level_id.to_string());
+}
+
+/// Starts a pushable sliding when the player walks into it and the tile behind it is free
+fn push(world: &mut World, tilemap: &TileMap, direction: Direction, tile_size: u32) {
+    let pushed_tiles = world
+        .query::<With<(&Position, &Movement), &Player>>()
+        .iter()
+        .filter(|(_, (_, movement))| movement.forced_step.is_none())
+        .filter_map(|(_, (position, _))| position.aligned_tile(tile_size))
+        .map(|(x, y)| (x + direction.x() as i64, y + direction.y() as i64))
+        .collect::<Vec<_>>();
+
+    if pushed_tiles.is_empty() {
+        return;
+    }
+
+    let occupied = occupied_tiles(world, tile_size);
+
+    for (_, (position, movement)) in
+        world.query_mut::<With<(&Position, &mut Movement), &Pushable>>()
+    {
+        if movement.forced_step.is_some() {
+            continue;
+        }
+
+        let Some(tile) = position.aligned_tile(tile_size) else {
+            continue;
+        };
+
+        if !pushed_tiles.contains(&tile) {
+            continue;
+        }
+
+        let destination = (tile.0 + direction.x() as i64, tile.1 + direction.y() as i64);
+        let can_move = !occupied.contains(&destination)
+            && tilemap
+                .tile_at(destination)
+                .is_some_and(|tile| can_enter(MovementMode::Walking, tile));
+
+        if can_move {
+            movement.direction = direction;
+            movement.forced_step = Some(destination);
+        }
+    }
+}
+
+fn update_pressure_plates(
+    world: &World,
+    tilemap: &TileMap,
+    game_flags: &mut GameFlags,
+    tile_size: u32,
+) {
+    let resting_tiles = world
+        .query::<With<(&Position, &Movement), &Pushable>>()
+        .iter()
+        .filter(|(_, (_, movement))| movement.forced_step.is_none())
+        .filter_map(|(_, (position, _))| position.aligned_tile(tile_size))
+        .collect::<HashSet<_>>();
+
+    for (tile, flag) in &tilemap.current_level().pressure_plates {
+        game_flags.set(flag, resting_tiles.contains(tile));
+    }
+}
+
+/// Draws pushables with the tile they are displayed with in LDtk
+pub struct PushableRenderSystem;
+
+impl System for PushableRenderSystem {
+    fn update(
+        &self,
+        world: &mut World,
+        resources: &mut Resources,
+        _: &mut Pixels,
+        _: &Input,
+        _: Duration,
+    ) {
+        let camera = &resources.camera;
+        let tilemap = &resources.tilemap;
+        let current_level = tilemap.current_level();
+
+        for (_, (pushable, position)) in world.query::<(&Pushable, &Position)>().iter() {
+            let Some(tile) =
+                tilemap.entities.get(&pushable.entity_id).and_then(|entity| entity.tile)
+            else {
+                continue;
+            };
+            let Some(tileset) = current_level.tilesets.get(&tile.tileset_id) else {
+                continue;
+            };
+
+            if !camera.is_visible(*position, tilemap.tilesize() as f32) {
+                continue;
+            }
+
+            let screen_pos = camera.world_to_screen(*position);
+            let command = tile_command(
+                tileset,
+                tile.tileset_position,
+                tilemap.tilesize(),
+                screen_pos.x.round() as i64,
+                screen_pos.y.round() as i64,
+                TileDrawParams::default(),
+            );
+            resources.render_queue.submit(RenderLayer::Objects, position.y, command);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    const TILE_SIZE: u32 = 16;
+
+    /// The Start level of the small fixture with its boulder at (1, 1) and the player on `tile`
+    fn start_level(player_tile: (i64, i64)) -> (World, TileMap, PushableState) {
+        let tilemap = test_fixtures::load(test_fixtures::SMALL);
+        let mut world = World::new();
+        let mut state = PushableState::default();
+        spawn_pushables(&mut world, &mut state, &tilemap, TILE_SIZE);
+        world.spawn((
+            Player,
+            Position::at_tile(player_tile.0, player_tile.1, TILE_SIZE),
+            Movement::new(60.0),
+        ));
+        (world, tilemap, state)
+    }
+
+    fn boulder(world: &World) -> (Position, Option<(i64, i64)>) {
+        let mut query = world.query::<(&Position, &Movement, &Pushable)>();
+        let (_, (position, movement, _)) = query.iter().next().unwrap();
+        (*position, movement.forced_step)
+    }
+
+    fn move_boulder(world: &mut World, tile: (i64, i64)) {
+        for (_, (position, _)) in world.query_mut::<(&mut Position, &Pushable)>() {
+            *position = Position::at_tile(tile.0, tile.1, TILE_SIZE);
+        }
+    }
+
+    #[test]
+    fn pushing_a_boulder_slides_it_onto_the_next_free_tile() {
+        let (mut world, tilemap, _) = start_level((0, 1));
+
+        push(&mut world, &tilemap, Direction::Right, TILE_SIZE);
+
+        assert_eq!(boulder(&world).1, Some((2, 1)));
+    }
+
+    #[test]
+    fn pushing_a_boulder_into_a_wall_or_off_the_level_is_blocked() {
+        let (mut world, tilemap, _) = start_level((1, 2));
+        push(&mut world, &tilemap, Direction::Up, TILE_SIZE);
+        assert_eq!(boulder(&world).1, None);
+
+        let (mut world, tilemap, _) = start_level((2, 1));
+        move_boulder(&mut world, (1, 1));
+        push(&mut world, &tilemap, Direction::Left, TILE_SIZE);
+        assert_eq!(boulder(&world).1, Some((0, 1)));
+
+        let (mut world, tilemap, _) = start_level((1, 1));
+        move_boulder(&mut world, (0, 1));
+        push(&mut world, &tilemap, Direction::Left, TILE_SIZE);
+        assert_eq!(boulder(&world).1, None);
+    }
+
+    #[test]
+    fn pushing_a_boulder_into_another_pushable_is_blocked() {
+        let (mut world, tilemap, _) = start_level((0, 1));
+        world.spawn((
+            Pushable {
+                entity_id: "second".to_string(),
+            },
+            Position::at_tile(2, 1, TILE_SIZE),
+            Movement::new(PUSHABLE_SPEED),
+        ));
+
+        push(&mut world, &tilemap, Direction::Right, TILE_SIZE);
+
+        assert!(world
+            .query::<&Movement>()
+            .with::<&Pushable>()
+            .iter()
+            .all(|(_, movement)| movement.forced_step.is_none()));
+    }
+
+    #[test]
+    fn plate_flag_is_set_only_while_a_boulder_rests_on_it() {
+        let (mut world, tilemap, _) = start_level((0, 1));
+        let mut game_flags = GameFlags::default();
+
+        update_pressure_plates(&world, &tilemap, &mut game_flags, TILE_SIZE);
+        assert!(!game_flags.is_set("plate_pressed"));
+
+        // Still sliding onto the plate
+        push(&mut world, &tilemap, Direction::Right, TILE_SIZE);
+        move_boulder(&mut world, (2, 1));
+        update_pressure_plates(&world, &tilemap, &mut game_flags, TILE_SIZE);
+        assert!(!game_flags.is_set("plate_pressed"));
+
+        for (_, movement) in world.query_mut::<&mut Movement>().with::<&Pushable>() {
+            movement.forced_step = None;
+        }
+        update_pressure_plates(&world, &tilemap, &mut game_flags, TILE_SIZE);
+        assert!(game_flags.is_set("plate_pressed"));
+
+        move_boulder(&mut world, (2, 2));
+        update_pressure_plates(&world, &tilemap, &mut game_flags, TILE_SIZE);
+        assert!(!game_flags.is_set("plate_pressed"));
+    }
+
+    #[test]
+    fn boulders_stay_where_they_were_left_after_a_level_change() {
+        let (mut world, mut tilemap, mut state) = start_level((0, 1));
+        move_boulder(&mut world, (2, 2));
+
+        tilemap.change_level("house").unwrap();
+        spawn_pushables(&mut world, &mut state, &tilemap, TILE_SIZE);
+        assert_eq!(world.query::<&Pushable>().iter().count(), 0);
+
+        tilemap.change_level("start").unwrap();
+        spawn_pushables(&mut world, &mut state, &tilemap, TILE_SIZE);
+        assert_eq!(boulder(&world).0.tile(TILE_SIZE), (2, 2));
+    }
+}
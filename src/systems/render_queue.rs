@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use pixels::Pixels;
+
+use crate::{
+    assets::{Handle, Image},
+    input::Input,
+    resource::Resources,
+    spritesheet::{blit, DrawParams, SpriteRegion},
+};
+
+use super::System;
+
+/// Layers are drawn from bottom to top, and within a layer by sort key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RenderLayer {
+    /// Tile layers below sprites
+    Ground,
+    /// Characters, pushables and effects, sorted by the y position they stand on so the one in
+    /// front covers the one behind
+    Objects,
+    /// Tile layers above sprites, like roofs and tree tops
+    Overhead,
+}
+
+/// A region of an image to draw at a screen position
+#[derive(Debug, Clone)]
+pub struct DrawCommand {
+    pub image: Handle<Image>,
+    pub region: SpriteRegion,
+    pub x: i32,
+    pub y: i32,
+    pub params: DrawParams,
+}
+
+// Resource
+/// Draw commands submitted by the render systems during a frame. Commands with the same layer and
+/// sort key are drawn in the order they were submitted.
+#[derive(Debug, Default)]
+pub struct RenderQueue {
+    commands: Vec<(RenderLayer, f32, DrawCommand)>,
+}
+
+impl RenderQueue {
+    pub fn submit(&mut self, layer: RenderLayer, sort_key: f32, command: DrawCommand) {
+        self.commands.push((layer, sort_key, command));
+    }
+
+    /// Draws every submitted command in order to a frame of the given width and empties the queue
+    pub fn flush(&mut self, frame: &mut [u8], frame_width: u32) {
+        // Stable, so tiles keep the order of their layers and stacks
+        self.commands.sort_by(|(layer_a, key_a, _), (layer_b, key_b, _)| {
+            layer_a.cmp(layer_b).then(key_a.total_cmp(key_b))
+        });
+
+        for (_, _, command) in self.commands.drain(..) {
+            blit(
+                &command.image,
+                command.region,
+                frame,
+                frame_width,
+                command.x,
+                command.y,
+                &command.params,
+            );
+        }
+    }
+}
+
+/// Draws everything submitted to the render queue this frame. Registered after the systems that
+/// submit and before the ones that change the finished frame, like lighting.
+pub struct RenderQueueSystem;
+
+impl System for RenderQueueSystem {
+    fn update(
+        &self,
+        _: &mut hecs::World,
+        resources: &mut Resources,
+        pixels: &mut Pixels,
+        _: &Input,
+        _: Duration,
+    ) {
+        resources.render_queue.flush(pixels.frame_mut(), resources.config.screen_width);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(color: [u8; 4]) -> DrawCommand {
+        DrawCommand {
+            image: Handle::new("pixel.png", Image::from_pixel(1, 1, image::Rgba(color))),
+            region: SpriteRegion {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+            },
+            x: 0,
+            y: 0,
+            params: DrawParams::default(),
+        }
+    }
+
+    #[test]
+    fn draws_by_layer_then_sort_key_then_submission() {
+        let mut frame = vec![0; 16 * 16 * 4];
+        let mut queue = RenderQueue::default();
+
+        queue.submit(RenderLayer::Overhead, 0.0, pixel([0, 0, 255, 255]));
+        queue.submit(RenderLayer::Objects, 32.0, pixel([255, 0, 0, 255]));
+        queue.submit(RenderLayer::Objects, 16.0, pixel([0, 255, 0, 255]));
+        queue.flush(&mut frame, 16);
+        assert_eq!(frame[..4], [0, 0, 255, 255]);
+
+        // The character further down stands in front
+        queue.submit(RenderLayer::Objects, 32.0, pixel([255, 0, 0, 255]));
+        queue.submit(RenderLayer::Objects, 16.0, pixel([0, 255, 0, 255]));
+        queue.submit(RenderLayer::Ground, 0.0, pixel([9, 9, 9, 255]));
+        queue.submit(RenderLayer::Ground, 0.0, pixel([1, 1, 1, 255]));
+        queue.flush(&mut frame, 16);
+        assert_eq!(frame[..4], [255, 0, 0, 255]);
+
+        queue.submit(RenderLayer::Ground, 0.0, pixel([9, 9, 9, 255]));
+        queue.submit(RenderLayer::Ground, 0.0, pixel([1, 1, 1, 255]));
+        queue.flush(&mut frame, 16);
+        assert_eq!(frame[..4], [1, 1, 1, 255]);
+    }
+}
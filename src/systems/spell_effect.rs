@@ -1,6 +1,6 @@
-use super::System;
+use super::{render_queue::RenderLayer, sprite_render::sprite_command, System};
 use crate::{
-    components::{Position, SpellEffect},
+    components::{AnimatedSprite, Position, SpellEffect},
     input::Input,
     resource::Resources,
 };
@@ -15,43 +15,33 @@ impl System for SpellEffectRenderSystem {
         &self,
         world: &mut World,
         resources: &mut Resources,
-        pixels: &mut Pixels,
+        _: &mut Pixels,
         _: &Input,
         delta_time: Duration,
     ) {
-        for (_, (effect, position)) in world.query_mut::<(&mut SpellEffect, &Position)>() {
-            effect.frame_time += delta_time.as_secs_f32();
-
-            if effect.frame_time >= 0.06 {
-                let cloned_effect = effect.clone();
-                let frames = cloned_effect.get_sprite_frames();
-                effect.current_frame += 1;
-                effect.frame_time = 0.0;
-
-                if effect.current_frame >= frames.len() {
-                    effect.is_finished = true;
-                }
-            }
+        let mut to_remove = Vec::new();
 
-            if !effect.is_finished {
-                let frames = effect.get_sprite_frames();
-                let (sprite_x, sprite_y) = frames[effect.current_frame % frames.len()];
-                let screen_pos = resources.camera.world_to_screen(*position);
+        for (entity, (_, sprite, position)) in
+            world.query_mut::<(&SpellEffect, &mut AnimatedSprite, &Position)>()
+        {
+            let clip =
+                resources.animations.get(&sprite.animations).and_then(|set| set.clip(&sprite.clip));
+            let Some(clip) = clip else {
+                // Nothing to play, so the effect would never finish
+                to_remove.push(entity);
+                continue;
+            };
 
-                resources.effects_spritesheet.0.draw_sprite_to_buffer(
-                    sprite_x,
-                    sprite_y,
-                    pixels.frame_mut(),
-                    screen_pos.x.round() as i32,
-                    screen_pos.y.round() as i32,
-                );
+            clip.advance(&mut sprite.playback, delta_time);
+            if sprite.playback.finished {
+                to_remove.push(entity);
+                continue;
             }
-        }
 
-        let mut to_remove = Vec::new();
-        for (entity, effect) in world.query::<&SpellEffect>().iter() {
-            if effect.is_finished {
-                to_remove.push(entity);
+            let command =
+                sprite_command(sprite, *position, &resources.camera, &resources.animations);
+            if let Some(command) = command {
+                resources.render_queue.submit(RenderLayer::Objects, position.y, command);
             }
         }
 
@@ -1,16 +1,22 @@
 use std::time::Duration;
 
+use hecs::With;
 use pixels::Pixels;
 
 use crate::{
+    animation::AnimationLibrary,
     camera::Camera,
-    components::{AnimatedSprite, Movement, Position, SpriteType},
+    components::{AnimatedSprite, Movement, Position},
     input::Input,
-    movement_util::Direction,
-    resource::{CharacterSpritesheet, Resources},
+    resource::Resources,
+    spritesheet::DrawParams,
+    vec2::Vec2,
 };
 
-use super::System;
+use super::{
+    render_queue::{DrawCommand, RenderLayer},
+    System,
+};
 
 pub struct SpriteRenderSystem;
 
@@ -19,63 +25,58 @@ impl System for SpriteRenderSystem {
         &self,
         world: &mut hecs::World,
         resources: &mut Resources,
-        pixels: &mut Pixels,
-        input: &Input,
+        _: &mut Pixels,
+        _: &Input,
         _delta_time: Duration,
     ) {
-        for (_, (sprite, position, movement)) in
-            world.query_mut::<(&AnimatedSprite, &Position, &Movement)>()
+        for (_, (sprite, position)) in
+            world.query_mut::<With<(&AnimatedSprite, &Position), &Movement>>()
         {
-            let frame = pixels.frame_mut();
-
-            draw_sprite(
+            // Characters stand a little above the tile they are on
+            let command = sprite_command(
                 sprite,
-                position,
-                Some(movement),
+                *position + Vec2::new(0.0, -4.0),
                 &resources.camera,
-                &mut resources.character_spritesheet,
-                frame,
-                input,
+                &resources.animations,
             );
+            if let Some(command) = command {
+                resources.render_queue.submit(RenderLayer::Objects, position.y, command);
+            }
         }
     }
 }
 
-fn draw_sprite(
+/// Command that draws the current frame of an animated sprite, if it is on screen and its
+/// animation set and clip are loaded
+pub fn sprite_command(
     sprite: &AnimatedSprite,
-    position: &Position,
-    movement: Option<&Movement>,
+    position: Position,
     camera: &Camera,
-    spritesheet: &mut CharacterSpritesheet,
-    frame: &mut [u8],
-    input: &Input,
-) {
-    if !camera.is_visible(*position) {
-        return;
-    }
+    animations: &AnimationLibrary,
+) -> Option<DrawCommand> {
+    let set = animations.get(&sprite.animations)?;
+    let sprite_frame = set.clip(&sprite.clip).and_then(|clip| clip.frame(&sprite.playback))?;
 
-    let sheet = match sprite.sprite_type {
-        SpriteType::Player => spritesheet,
-    };
+    // Far enough around the screen for any part of the frame to show
+    let (offset_x, offset_y) = sprite_frame.offset;
+    let size = sprite_frame.region.width.max(sprite_frame.region.height) as i32;
+    let margin = size + offset_x.abs().max(offset_y.abs());
+    if !camera.is_visible(position, margin as f32) {
+        return None;
+    }
 
-    let (direction, is_moving) = if let Some(movement) = movement {
-        (
-            &movement.direction,
-            movement.is_moving || input.x() != 0 || input.y() != 0,
-        )
-    } else {
-        (&Direction::Down, false)
+    let screen_pos = camera.world_to_screen(position);
+    // A flipped frame of a flipped sprite is drawn the right way round
+    let params = DrawParams {
+        flip_x: sprite.params.flip_x != sprite_frame.flip_x,
+        flip_y: sprite.params.flip_y != sprite_frame.flip_y,
+        ..sprite.params.clone()
     };
-
-    let vertical_offset = -4.0;
-    let (sprite_x, sprite_y) = sprite.get_current_frame(direction, is_moving);
-    let screen_pos = camera.world_to_screen(*position);
-
-    sheet.0.draw_sprite_to_buffer(
-        sprite_x,
-        sprite_y,
-        frame,
-        screen_pos.x.round() as i32,
-        (screen_pos.y + vertical_offset).round() as i32,
-    );
+    Some(DrawCommand {
+        image: set.spritesheet.image().clone(),
+        region: set.spritesheet.region(sprite_frame.region)?,
+        x: screen_pos.x.round() as i32 + sprite_frame.offset.0,
+        y: screen_pos.y.round() as i32 + sprite_frame.offset.1,
+        params,
+    })
 }
@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use hecs::With;
+use pixels::Pixels;
+
+use crate::{
+    components::{Movement, MovementMode, Position, Surf},
+    input::Input,
+    resource::Resources,
+    tile::Terrain,
+};
+
+use super::System;
+
+pub struct SurfSystem;
+
+impl System for SurfSystem {
+    fn update(
+        &self,
+        world: &mut hecs::World,
+        resources: &mut Resources,
+        _: &mut Pixels,
+        input: &Input,
+        _: Duration,
+    ) {
+        let tilemap = &resources.tilemap;
+        let tile_size = resources.config.tile_size;
+
+        for (_, (position, movement)) in
+            world.query_mut::<With<(&Position, &mut Movement), &Surf>>()
+        {
+            match movement.mode {
+                MovementMode::Walking => {
+                    if !input.e() || movement.is_moving || movement.forced_step.is_some() {
+                        continue;
+                    }
+
+                    let Some((tile_x, tile_y)) = position.aligned_tile(tile_size) else {
+                        continue;
+                    };
+                    let facing_tile = (
+                        tile_x + movement.direction.x() as i64,
+                        tile_y + movement.direction.y() as i64,
+                    );
+
+                    if tilemap
+                        .tile_at(facing_tile)
+                        .is_some_and(|tile| tile.terrain == Terrain::Water)
+                    {
+                        movement.mode = MovementMode::Surfing;
+                        movement.forced_step = Some(facing_tile);
+                    }
+                }
+                MovementMode::Surfing => {
+                    if movement.forced_step.is_some() {
+                        continue;
+                    }
+
+                    // Walking onto a shore tile takes the entity back onto land
+                    let on_land = tilemap
+                        .tile_at(position.nearest_tile(tile_size))
+                        .is_some_and(|tile| tile.terrain != Terrain::Water);
+
+                    if on_land {
+                        movement.mode = MovementMode::Walking;
+                    }
+                }
+            }
+        }
+    }
+}
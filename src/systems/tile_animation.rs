@@ -15,12 +15,13 @@ impl System for TileAnimationSystem {
         _: &Input,
         delta_time: Duration,
     ) {
-        let current_level = resources.tilemap.current_level_mut();
+        for level_id in resources.tilemap.levels_in_view() {
+            let Some(level) = resources.tilemap.get_level_mut(&level_id) else {
+                continue;
+            };
 
-        for tile_data in current_level.tiles.values_mut() {
-            if let Some(animation) = &mut tile_data.animation {
-                animation.update(delta_time);
-                tile_data.tileset_position = animation.current_position();
+            for layer in &mut level.layers {
+                layer.update_animations(delta_time);
             }
         }
     }
@@ -1,9 +1,21 @@
 use std::time::Duration;
 
-use crate::{input::Input, resource::Resources, tile::TileData, SCREEN_HEIGHT, SCREEN_WIDTH};
-
-use super::System;
-
+use crate::{
+    camera::Camera,
+    input::Input,
+    ivec2::IVec2,
+    resource::Resources,
+    spritesheet::{DrawParams, SpriteRegion},
+    tile::{LayerPlacement, Level, TileLayer, Tileset},
+    vec2::Vec2,
+};
+
+use super::{
+    render_queue::{DrawCommand, RenderLayer},
+    System,
+};
+
+/// Draws the tile layers that go below sprites
 pub struct TileRenderSystem;
 
 impl System for TileRenderSystem {
@@ -11,84 +23,164 @@ impl System for TileRenderSystem {
         &self,
         _: &mut hecs::World,
         resources: &mut Resources,
-        pixels: &mut pixels::Pixels,
+        _: &mut pixels::Pixels,
         _: &Input,
         _: Duration,
     ) {
-        let camera = &resources.camera;
-        let tilemap = &resources.tilemap;
-        let tilesize = tilemap.tilesize();
-        let current_level = tilemap.current_level();
+        draw_levels(resources, LayerPlacement::BelowSprites);
+    }
+}
 
-        let frame = pixels.frame_mut();
+/// Draws the tile layers that go above sprites, like roofs and tree tops
+pub struct TileOverlayRenderSystem;
 
-        let camera_left = camera.position().x - (SCREEN_WIDTH as f32 / 2.0);
-        let camera_top = camera.position().y - (SCREEN_HEIGHT as f32 / 2.0);
+impl System for TileOverlayRenderSystem {
+    fn update(
+        &self,
+        _: &mut hecs::World,
+        resources: &mut Resources,
+        _: &mut pixels::Pixels,
+        _: &Input,
+        _: Duration,
+    ) {
+        draw_levels(resources, LayerPlacement::AboveSprites);
+    }
+}
 
-        // Calculate visible tile range based on camera's top-left position
-        let start_tile_x = (camera_left / tilesize as f32).floor() as i64;
-        let start_tile_y = (camera_top / tilesize as f32).floor() as i64;
-        let columns = (SCREEN_WIDTH / tilesize as u32) as i64 + 1;
-        let rows = (SCREEN_HEIGHT / tilesize as u32) as i64 + 1;
+fn draw_levels(resources: &mut Resources, placement: LayerPlacement) {
+    let camera = &resources.camera;
+    let tilemap = &resources.tilemap;
+    let queue = &mut resources.render_queue;
+    let layer = match placement {
+        LayerPlacement::BelowSprites => RenderLayer::Ground,
+        LayerPlacement::AboveSprites => RenderLayer::Overhead,
+    };
+    let mut draw = |level: &Level, tile_layer: &TileLayer, offset: IVec2| {
+        let mut submit = |command| queue.submit(layer, 0.0, command);
+        draw_layer(
+            &mut submit,
+            camera,
+            level,
+            tile_layer,
+            offset,
+            tilemap.tilesize(),
+        );
+    };
+
+    for tile_layer in tilemap.current_level().layers_with_placement(placement) {
+        draw(tilemap.current_level(), tile_layer, IVec2::ZERO);
+    }
+
+    for (_, level, offset) in tilemap.streamed_neighbours() {
+        for tile_layer in level.layers_with_placement(placement) {
+            draw(level, tile_layer, offset);
+        }
+    }
+}
 
-        // Iterate through visible tiles
-        for y in start_tile_y..(start_tile_y + rows) {
-            for x in start_tile_x..(start_tile_x + columns) {
-                let Some(tile) = current_level.tiles.get(&(x, y)) else {
-                    continue;
-                };
+/// Submits the visible tiles of a layer in a level placed at the given pixel offset from the
+/// current level
+fn draw_layer(
+    submit: &mut impl FnMut(DrawCommand),
+    camera: &Camera,
+    level: &Level,
+    layer: &TileLayer,
+    offset: IVec2,
+    tilesize: i64,
+) {
+    let Some(tileset) = level.tilesets.get(&layer.tileset_id) else {
+        return;
+    };
+    let offset = offset + layer.offset;
+
+    let (width, height) = camera.viewport();
+    let top_left = camera.screen_to_world(Vec2::ZERO);
+    let camera_left = top_left.x - offset.x as f32;
+    let camera_top = top_left.y - offset.y as f32;
+
+    // Calculate visible tile range based on camera's top-left position
+    let start_tile_x = (camera_left / tilesize as f32).floor() as i64;
+    let start_tile_y = (camera_top / tilesize as f32).floor() as i64;
+    // One more than fit on screen, for the partly visible tiles at both edges
+    let columns = width.div_ceil(tilesize as u32) as i64 + 1;
+    let rows = height.div_ceil(tilesize as u32) as i64 + 1;
+
+    // Iterate through visible tiles
+    for y in start_tile_y..(start_tile_y + rows) {
+        for x in start_tile_x..(start_tile_x + columns) {
+            let Some(stack) = layer.tiles.get((x, y)) else {
+                continue;
+            };
 
-                let screen_pos = camera.world_to_screen(tile.position.into());
+            for tile in stack {
+                let screen_pos = camera.world_to_screen((tile.position + offset).into());
 
                 // Round to prevent subpixel positioning
                 // This is required to prevent off by one pixel jitter when moving up or left
                 let screen_x = screen_pos.x.round() as i64;
                 let screen_y = screen_pos.y.round() as i64;
 
-                draw_tile(
-                    frame,
-                    &current_level.tileset_pixels,
-                    current_level.tileset_width,
-                    tile,
+                submit(tile_command(
+                    tileset,
+                    tile.tileset_position,
                     tilesize,
                     screen_x,
                     screen_y,
-                );
+                    TileDrawParams {
+                        flip_x: tile.flip_x,
+                        flip_y: tile.flip_y,
+                        opacity: tile.alpha * layer.opacity,
+                    },
+                ));
             }
         }
     }
 }
 
-fn draw_tile(
-    frame: &mut [u8],
-    tileset: &[u8],
-    tileset_width: u32,
-    tile: &TileData,
+/// How a tile is drawn from its tileset
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TileDrawParams {
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub opacity: f32,
+}
+
+impl Default for TileDrawParams {
+    fn default() -> Self {
+        Self {
+            flip_x: false,
+            flip_y: false,
+            opacity: 1.0,
+        }
+    }
+}
+
+/// Command that draws a tile of the tileset with its top left corner at the screen position
+pub(crate) fn tile_command(
+    tileset: &Tileset,
+    tileset_position: IVec2,
     tilesize: i64,
     screen_x: i64,
     screen_y: i64,
-) {
-    for y in 0..tilesize {
-        for x in 0..tilesize {
-            let screen_pixel_x = screen_x + x;
-            let screen_pixel_y = screen_y + y;
-
-            // Skip if pixel would be off screen
-            if screen_pixel_x < 0
-                || screen_pixel_y < 0
-                || screen_pixel_x >= SCREEN_WIDTH as i64
-                || screen_pixel_y >= SCREEN_HEIGHT as i64
-            {
-                continue;
-            }
-
-            let src_pixel_x = tile.tileset_position.x + x;
-            let src_pixel_y = tile.tileset_position.y + y;
-
-            let src_idx = ((src_pixel_y * tileset_width as i64 + src_pixel_x) * 4) as usize;
-            let dst_idx = ((screen_pixel_y * SCREEN_WIDTH as i64 + screen_pixel_x) * 4) as usize;
-
-            frame[dst_idx..dst_idx + 4].copy_from_slice(&tileset[src_idx..src_idx + 4]);
-        }
+    params: TileDrawParams,
+) -> DrawCommand {
+    let position = tileset.origin + tileset_position;
+    let region = SpriteRegion {
+        x: position.x as u32,
+        y: position.y as u32,
+        width: tilesize as u32,
+        height: tilesize as u32,
+    };
+    DrawCommand {
+        image: tileset.image.clone(),
+        region,
+        x: screen_x as i32,
+        y: screen_y as i32,
+        params: DrawParams {
+            flip_x: params.flip_x,
+            flip_y: params.flip_y,
+            alpha: params.opacity,
+            ..Default::default()
+        },
     }
 }
@@ -0,0 +1,116 @@
+//! Small LDtk and Tiled projects in `tests/fixtures` that unit tests load
+
+use std::{
+    env, fs,
+    ops::Deref,
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde_json::Value;
+
+use crate::{
+    assets::AssetSource,
+    tile::{LoadOptions, TileMap, TileMapError},
+};
+
+/// Outdoor `Start` level with a boulder next to a pressure plate and a door to `House`
+pub const SMALL: &str = "small.ldtk";
+/// Tiled map with a wall and water in its Collision layer and an animated tile, using the tileset
+/// in `small.tsx`
+pub const SMALL_TILED: &str = "small.tmx";
+/// Tiled map with ice, a conveyor and a spinner in its Collision layer, using `terrain.tsx`
+pub const TERRAIN_TILED: &str = "terrain.tmx";
+/// Animation on the grid of the tiles in `tiles.png`
+pub const TILE_ANIMATIONS: &str = "tiles.json";
+
+pub fn directory() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+pub fn source() -> AssetSource {
+    AssetSource::Directory(directory())
+}
+
+pub fn load(path: &str) -> TileMap {
+    TileMap::load_from(&source(), path, LoadOptions::default()).unwrap()
+}
+
+/// Loads a copy of an LDtk project after changing its JSON, for testing how broken projects are
+/// reported
+pub fn load_modified(path: &str, change: impl FnOnce(&mut Value)) -> Result<TileMap, TileMapError> {
+    TileMap::load_from(&modified(path, change), path, LoadOptions::default())
+}
+
+/// Loads a copy of the fixtures with a piece of text replaced in one of them, for the Tiled files
+pub fn load_replaced(
+    path: &str,
+    file: &str,
+    from: &str,
+    to: &str,
+) -> Result<TileMap, TileMapError> {
+    let contents = fs::read_to_string(directory().join(file)).unwrap();
+    assert!(
+        contents.contains(from),
+        "{} does not contain {:?}",
+        file,
+        from
+    );
+    let source = copy_with(file, &contents.replacen(from, to, 1));
+    TileMap::load_from(&source, path, LoadOptions::default())
+}
+
+/// Copies the fixtures to a new temporary directory and changes the JSON of one of them
+pub fn modified(path: &str, change: impl FnOnce(&mut Value)) -> FixtureCopy {
+    let mut json: Value =
+        serde_json::from_str(&fs::read_to_string(directory().join(path)).unwrap()).unwrap();
+    change(&mut json);
+    copy_with(path, &serde_json::to_string(&json).unwrap())
+}
+
+/// Copy of the fixtures in a temporary directory, which is removed when the copy is dropped
+pub struct FixtureCopy {
+    source: AssetSource,
+}
+
+impl Deref for FixtureCopy {
+    type Target = AssetSource;
+
+    fn deref(&self) -> &AssetSource {
+        &self.source
+    }
+}
+
+impl Drop for FixtureCopy {
+    fn drop(&mut self) {
+        if let Some(directory) = self.source.directory() {
+            // Leaving the copy behind doesn't affect other tests, so failures are ignored
+            let _ = fs::remove_dir_all(directory);
+        }
+    }
+}
+
+/// Copies the fixtures to a new temporary directory, with different contents for one file or an
+/// extra file, like a config
+pub fn copy_with(path: &str, contents: &str) -> FixtureCopy {
+    static COPIES: AtomicUsize = AtomicUsize::new(0);
+
+    let copy = env::temp_dir().join(format!(
+        "pixels-engine-fixtures-{}-{}",
+        process::id(),
+        COPIES.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&copy).unwrap();
+    for entry in fs::read_dir(directory()).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_type().unwrap().is_file() {
+            fs::copy(entry.path(), copy.join(entry.file_name())).unwrap();
+        }
+    }
+    fs::write(copy.join(path), contents).unwrap();
+
+    FixtureCopy {
+        source: AssetSource::Directory(copy),
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use crate::{
+    assets::{normalize, AssetSource, Handle, Image, ImageCache},
+    atlas::Atlas,
+    ivec2::IVec2,
+};
+
+use super::{TileMapError, Tileset};
+
+/// Tilesets keyed by image path, so levels using the same tileset share one copy. The images come
+/// from an [`ImageCache`], so the game and its maps decode each image once. Clones share the same
+/// cache and can be handed to the prefetch thread.
+#[derive(Debug, Clone, Default)]
+pub struct TilesetCache {
+    images: ImageCache,
+    tilesets: Arc<Mutex<HashMap<String, Arc<Tileset>>>>,
+}
+
+impl TilesetCache {
+    /// A cache that loads tileset images through the image cache
+    pub fn new(images: ImageCache) -> Self {
+        Self {
+            images,
+            tilesets: Default::default(),
+        }
+    }
+
+    pub fn load(&self, path: &Path) -> Result<Arc<Tileset>, TileMapError> {
+        let path = normalize(path);
+        if let Some(tileset) = self.get(&path) {
+            return Ok(tileset);
+        }
+
+        let image =
+            self.images.load(&path).map_err(|error| TileMapError::TilesetImage(Box::new(error)))?;
+        let tileset = Arc::new(Tileset {
+            origin: IVec2::ZERO,
+            width: image.width(),
+            height: image.height(),
+            image,
+        });
+
+        Ok(self.lock().entry(path).or_insert(tileset).clone())
+    }
+
+    /// Source the tileset images are read from
+    pub fn source(&self) -> &AssetSource {
+        self.images.source()
+    }
+
+    /// Stands in a blank image for a tileset image that can't be loaded, so the levels using it can
+    /// still be loaded and checked
+    pub fn insert_blank(&self, path: &Path, width: u32, height: u32) {
+        let path = normalize(path);
+        let tileset = Tileset {
+            origin: IVec2::ZERO,
+            width,
+            height,
+            image: Handle::new(&path, Image::new(width, height)),
+        };
+        self.lock().insert(path, Arc::new(tileset));
+    }
+
+    /// The tileset if it has been loaded
+    pub fn get(&self, path: &str) -> Option<Arc<Tileset>> {
+        self.lock().get(path).cloned()
+    }
+
+    /// Reads tilesets packed into the atlas from its pages from now on, dropping their own images
+    /// once no level uses them
+    pub fn use_atlas(&self, atlas: &Atlas) {
+        let mut tilesets = self.lock();
+        for (path, page, region) in atlas.images() {
+            let tileset = Tileset {
+                image: page.clone(),
+                origin: IVec2::new(region.x as i64, region.y as i64),
+                width: region.width,
+                height: region.height,
+            };
+            tilesets.insert(path.to_string(), Arc::new(tileset));
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Tileset>>> {
+        // The map is never left half updated, so a panic on another thread doesn't invalidate it
+        self.tilesets.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, SMALL};
+
+    #[test]
+    fn levels_using_the_same_tileset_share_its_image() {
+        let tilemap = test_fixtures::load(SMALL);
+        let start = &tilemap.get_level("start").unwrap().tilesets[&1];
+        let east = &tilemap.get_level("east").unwrap().tilesets[&1];
+
+        assert!(Arc::ptr_eq(start, east));
+    }
+
+    #[test]
+    fn tileset_is_read_once() {
+        let cache = TilesetCache::new(ImageCache::new(test_fixtures::source()));
+
+        let first = cache.load(Path::new("./tiles.png")).unwrap();
+        let second = cache.load(Path::new("tiles.png")).unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!((first.width, first.height), (32, 32));
+    }
+}
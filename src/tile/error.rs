@@ -0,0 +1,174 @@
+use std::fmt;
+
+use crate::assets::AssetError;
+
+/// Everything that can go wrong while loading a tile map. Each variant carries enough context for
+/// a level designer to find the problem in LDtk.
+#[derive(Debug)]
+pub enum TileMapError {
+    Project {
+        path: String,
+        source: ldtk2::Error,
+    },
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    Xml {
+        path: String,
+        source: xml::reader::Error,
+    },
+    /// A Tiled map or tileset that can be parsed but not understood
+    InvalidTiled {
+        path: String,
+        reason: String,
+    },
+    NoLevels,
+    UnknownLevel {
+        level: String,
+    },
+    NoPlayerStart,
+    NoTileLayers {
+        level: String,
+    },
+    MissingLayers {
+        level: String,
+    },
+    MissingLayer {
+        level: String,
+        layer: String,
+    },
+    MissingTileset {
+        level: String,
+        layer: String,
+        tileset_id: Option<i64>,
+    },
+    MissingTilesetImage {
+        tileset: String,
+    },
+    /// A tileset image that can't be read or decoded. Boxed, as asset errors can hold map errors.
+    TilesetImage(Box<AssetError>),
+    InvalidField {
+        level: String,
+        entity_iid: String,
+        field: String,
+        reason: String,
+    },
+    InvalidTile {
+        level: String,
+        layer: String,
+        reason: String,
+    },
+    InvalidAnimation {
+        tileset: String,
+        tile_id: i64,
+        reason: String,
+    },
+    /// An outdoor level that is not placed on the tile grid of the world, so its tiles can't line
+    /// up with the levels streamed next to it
+    MisalignedLevel {
+        level: String,
+        world_x: i64,
+        world_y: i64,
+        tile_size: i64,
+    },
+}
+
+impl fmt::Display for TileMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TileMapError::Project { path, source } => {
+                write!(f, "Could not read LDtk project {}: {}", path, source)
+            }
+            TileMapError::Io { path, source } => write!(f, "Could not read {}: {}", path, source),
+            TileMapError::Xml { path, source } => write!(f, "Invalid XML in {}: {}", path, source),
+            TileMapError::InvalidTiled { path, reason } => {
+                write!(f, "Unsupported Tiled file {}: {}", path, reason)
+            }
+            TileMapError::NoLevels => write!(f, "The project has no levels"),
+            TileMapError::UnknownLevel { level } => write!(f, "There is no level {}", level),
+            TileMapError::NoPlayerStart => {
+                write!(f, "Could not find PlayerStart entity in any level")
+            }
+            TileMapError::NoTileLayers { level } => {
+                write!(f, "Level {} has no layer with a tileset", level)
+            }
+            TileMapError::MissingLayers { level } => {
+                write!(
+                    f,
+                    "Level {} has no layers, are levels saved in separate files?",
+                    level
+                )
+            }
+            TileMapError::MissingLayer { level, layer } => {
+                write!(f, "Level {} is missing the {} layer", level, layer)
+            }
+            TileMapError::MissingTileset {
+                level,
+                layer,
+                tileset_id,
+            } => match tileset_id {
+                Some(tileset_id) => write!(
+                    f,
+                    "Layer {} in level {} uses tileset {} which is not defined",
+                    layer, level, tileset_id
+                ),
+                None => write!(f, "Layer {} in level {} has no tileset", layer, level),
+            },
+            TileMapError::MissingTilesetImage { tileset } => {
+                write!(f, "Tileset {} has no image", tileset)
+            }
+            TileMapError::TilesetImage(error) => error.fmt(f),
+            TileMapError::InvalidField {
+                level,
+                entity_iid,
+                field,
+                reason,
+            } => write!(
+                f,
+                "Field {} of entity {} in level {} is invalid: {}",
+                field, entity_iid, level, reason
+            ),
+            TileMapError::InvalidTile {
+                level,
+                layer,
+                reason,
+            } => write!(
+                f,
+                "Invalid tile in layer {} of level {}: {}",
+                layer, level, reason
+            ),
+            TileMapError::InvalidAnimation {
+                tileset,
+                tile_id,
+                reason,
+            } => write!(
+                f,
+                "Invalid animation data on tile {} in tileset {}: {}",
+                tile_id, tileset, reason
+            ),
+            TileMapError::MisalignedLevel {
+                level,
+                world_x,
+                world_y,
+                tile_size,
+            } => write!(
+                f,
+                "Level {} is at {},{} in the world, which is not on the {}px tile grid",
+                level, world_x, world_y, tile_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TileMapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TileMapError::Project { source, .. } => Some(source),
+            TileMapError::TilesetImage(error) => Some(error),
+            TileMapError::Io { source, .. } => Some(source),
+            TileMapError::Xml { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+/// Dense storage with one value per cell of a level, indexed by tile coordinate. Lookups outside
+/// the grid return `None` rather than panicking, so callers can probe neighbouring cells freely.
+#[derive(Debug, Clone)]
+pub struct TileGrid<T> {
+    width: i64,
+    height: i64,
+    cells: Vec<T>,
+}
+
+impl<T> TileGrid<T> {
+    /// Creates a grid with the value of each cell produced from its coordinate
+    pub fn from_fn(width: i64, height: i64, mut f: impl FnMut((i64, i64)) -> T) -> Self {
+        let width = width.max(0);
+        let height = height.max(0);
+        let cells = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(&mut f).collect();
+
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    pub fn width(&self) -> i64 {
+        self.width
+    }
+
+    pub fn height(&self) -> i64 {
+        self.height
+    }
+
+    pub fn contains(&self, cell: (i64, i64)) -> bool {
+        self.index(cell).is_some()
+    }
+
+    pub fn get(&self, cell: (i64, i64)) -> Option<&T> {
+        self.cells.get(self.index(cell)?)
+    }
+
+    pub fn get_mut(&mut self, cell: (i64, i64)) -> Option<&mut T> {
+        let index = self.index(cell)?;
+        self.cells.get_mut(index)
+    }
+
+    /// Every cell with its coordinate, row by row
+    pub fn iter(&self) -> impl Iterator<Item = ((i64, i64), &T)> {
+        let width = self.width;
+        self.cells.iter().enumerate().map(move |(index, value)| {
+            let index = index as i64;
+            ((index % width, index / width), value)
+        })
+    }
+
+    fn index(&self, (x, y): (i64, i64)) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        Some((y * self.width + x) as usize)
+    }
+}
+
+impl<T: Default> TileGrid<T> {
+    pub fn new(width: i64, height: i64) -> Self {
+        Self::from_fn(width, height, |_| T::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups_outside_the_grid_return_none() {
+        let grid = TileGrid::from_fn(3, 2, |(x, y)| x + y * 10);
+
+        assert_eq!(grid.get((2, 1)), Some(&12));
+        assert_eq!(grid.get((3, 0)), None);
+        assert_eq!(grid.get((0, 2)), None);
+        assert_eq!(grid.get((-1, 0)), None);
+    }
+
+    #[test]
+    fn iter_yields_coordinates_row_by_row() {
+        let grid = TileGrid::from_fn(2, 2, |cell| cell);
+
+        assert!(grid.iter().all(|(cell, value)| cell == *value));
+        assert_eq!(grid.iter().nth(2).map(|(cell, _)| cell), Some((0, 1)));
+    }
+}
@@ -0,0 +1,1457 @@
+use crate::{
+    assets::{AssetSource, Handle, Image, ImageCache},
+    atlas::Atlas,
+    ivec2::IVec2,
+    movement_util::{Direction, Rotation},
+    vec2::Vec2,
+};
+use ldtk2::Ldtk;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+mod cache;
+mod error;
+mod grid;
+mod prefetch;
+mod tiled;
+
+pub use cache::TilesetCache;
+pub use error::TileMapError;
+pub use grid::TileGrid;
+use prefetch::{LevelSource, Prefetcher};
+
+type LevelId = String;
+type EntityId = String;
+pub type TilesetId = i64;
+
+#[derive(Debug)]
+pub struct TileMap {
+    levels: HashMap<LevelId, Level>,
+    current_level_id: LevelId,
+    tilesize: i64,
+    pub player_starting_position: Vec2,
+    pub entities: HashMap<EntityId, EntityInstance>,
+    /// Levels that are built on first use when loading lazily
+    pending: HashMap<LevelId, LevelSource>,
+    /// Levels that failed to build on the prefetch thread, reported when they are entered
+    failed: HashMap<LevelId, TileMapError>,
+    prefetcher: Option<Prefetcher>,
+    tilesets: TilesetCache,
+}
+
+/// How [`TileMap::load_with`] builds the levels of an LDtk project
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadOptions {
+    /// Build levels when they are first entered instead of upfront
+    pub lazy: bool,
+    /// When loading lazily, build the levels reachable from the current level on a background
+    /// thread
+    pub prefetch: bool,
+}
+
+#[derive(Debug)]
+pub struct Level {
+    /// Name of the level in LDtk
+    pub identifier: String,
+    /// Gameplay data for every cell of the Collision layer
+    pub tiles: TileGrid<TileData>,
+    /// Tile layers from bottom to top
+    pub layers: Vec<TileLayer>,
+    /// Tileset images, shared with other levels that use them
+    pub tilesets: HashMap<TilesetId, Arc<Tileset>>,
+    /// Game flag set while a pushable entity rests on the tile
+    pub pressure_plates: HashMap<(i64, i64), String>,
+    pub indoors: bool,
+    /// Position of the level's top left corner in the LDtk world
+    pub world_position: IVec2,
+    /// Size of the level in pixels
+    pub pixel_width: i64,
+    pub pixel_height: i64,
+    pub neighbours: Vec<LevelId>,
+}
+
+#[derive(Debug)]
+pub struct EntityInstance {
+    pub identifier: String,
+    pub position: Vec2,
+    pub level_id: String,
+    pub direction: Option<Direction>,
+    /// Tile the entity is displayed with in LDtk
+    pub tile: Option<EntityTile>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EntityTile {
+    pub tileset_id: TilesetId,
+    pub tileset_position: IVec2,
+}
+
+#[derive(Debug)]
+pub struct TileData {
+    pub position: IVec2,
+    pub traversable: bool,
+    pub terrain: Terrain,
+    pub transition: Option<Transition>,
+}
+
+#[derive(Debug)]
+pub struct Tileset {
+    /// Image the tiles are read from, either the tileset's own image or an atlas page it was
+    /// packed into
+    pub image: Handle<Image>,
+    /// Top left corner of the tileset in the image
+    pub origin: IVec2,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug)]
+pub struct TileLayer {
+    pub identifier: String,
+    pub placement: LayerPlacement,
+    pub tileset_id: TilesetId,
+    pub opacity: f32,
+    /// Pixel offset of the whole layer, added to the position of each tile
+    pub offset: IVec2,
+    // Cells can hold several stacked tiles, drawn in order
+    pub tiles: TileGrid<Vec<LayerTile>>,
+    /// Cells holding at least one animated tile, so animating doesn't visit every cell
+    animated_cells: Vec<(i64, i64)>,
+}
+
+impl TileLayer {
+    fn new(
+        identifier: String,
+        tileset_id: TilesetId,
+        opacity: f32,
+        offset: IVec2,
+        tiles: TileGrid<Vec<LayerTile>>,
+    ) -> Self {
+        let animated_cells = tiles
+            .iter()
+            .filter(|(_, stack)| stack.iter().any(|tile| tile.animation.is_some()))
+            .map(|(cell, _)| cell)
+            .collect();
+
+        Self {
+            placement: LayerPlacement::from_identifier(&identifier),
+            identifier,
+            tileset_id,
+            opacity,
+            offset,
+            tiles,
+            animated_cells,
+        }
+    }
+
+    /// Advances every animated tile in the layer
+    pub fn update_animations(&mut self, delta_time: Duration) {
+        for &cell in &self.animated_cells {
+            let Some(stack) = self.tiles.get_mut(cell) else {
+                continue;
+            };
+
+            for tile in stack {
+                if let Some(animation) = &mut tile.animation {
+                    animation.update(delta_time);
+                    tile.tileset_position = animation.current_position();
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LayerTile {
+    pub tileset_position: IVec2,
+    pub position: IVec2,
+    pub animation: Option<TileAnimation>,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub alpha: f32,
+}
+
+/// Whether a layer is drawn below or above sprites. Layers with an identifier starting with
+/// `Above`, e.g. `Above_Roofs`, are drawn on top of the player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayerPlacement {
+    BelowSprites,
+    AboveSprites,
+}
+
+impl LayerPlacement {
+    fn from_identifier(identifier: &str) -> Self {
+        if identifier.starts_with("Above") {
+            LayerPlacement::AboveSprites
+        } else {
+            LayerPlacement::BelowSprites
+        }
+    }
+}
+
+impl Level {
+    /// Doors stay closed until a transition opens them
+    fn close_doors(&mut self) {
+        let door_cells = self
+            .tiles
+            .iter()
+            .filter(|(_, tile)| tile.transition.as_ref().is_some_and(|transition| transition.door))
+            .map(|(cell, _)| cell)
+            .collect::<Vec<_>>();
+
+        for cell in door_cells {
+            if let Some(animation) = self.animation_at_mut(cell) {
+                animation.pause();
+            }
+        }
+    }
+
+    /// Animation of the top-most animated tile in a cell
+    pub fn animation_at_mut(&mut self, cell: (i64, i64)) -> Option<&mut TileAnimation> {
+        self.layers.iter_mut().rev().find_map(|layer| {
+            layer.tiles.get_mut(cell)?.iter_mut().rev().find_map(|tile| tile.animation.as_mut())
+        })
+    }
+
+    pub fn layers_with_placement(
+        &self,
+        placement: LayerPlacement,
+    ) -> impl Iterator<Item = &TileLayer> {
+        self.layers.iter().filter(move |layer| layer.placement == placement)
+    }
+}
+
+/// What kind of ground a tile is, read from the value identifiers of the Collision IntGrid layer
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Terrain {
+    #[default]
+    Ground,
+    Wall,
+    Water,
+    /// Entities keep sliding in their direction until they reach a tile that is not ice
+    Ice,
+    /// Pushes entities one tile in the given direction
+    Conveyor(Direction),
+    /// Turns entities and sends them off in their new direction
+    Spinner(Rotation),
+}
+
+impl Terrain {
+    fn from_int_grid_value(value: i64, layer_definition: Option<&ldtk2::LayerDefinition>) -> Self {
+        if value == 0 {
+            return Terrain::Ground;
+        }
+
+        let identifier = layer_definition
+            .and_then(|def| def.int_grid_values.iter().find(|v| v.value == value))
+            .and_then(|v| v.identifier.as_deref());
+
+        identifier.map_or(Terrain::Wall, Terrain::from_identifier)
+    }
+
+    /// Terrain named by an IntGrid value identifier in LDtk or a tile class in Tiled. Unknown
+    /// names are walls.
+    fn from_identifier(identifier: &str) -> Self {
+        match identifier {
+            "WaterBoundary" => Terrain::Water,
+            "Ice" => Terrain::Ice,
+            "Spinner_Clockwise" => Terrain::Spinner(Rotation::Clockwise),
+            "Spinner_CounterClockwise" => Terrain::Spinner(Rotation::CounterClockwise),
+            identifier => identifier
+                .strip_prefix("Conveyor_")
+                .and_then(|direction| Direction::from_str(direction).ok())
+                .map_or(Terrain::Wall, Terrain::Conveyor),
+        }
+    }
+
+    /// Whether the terrain can be entered on foot
+    pub fn is_walkable(&self) -> bool {
+        !matches!(self, Terrain::Wall | Terrain::Water)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Transition {
+    pub destination: EntityId,
+    pub style: TransitionStyle,
+    pub trigger: TransitionTrigger,
+    // Play the animation of the entrance tile before fading out
+    pub door: bool,
+    // Take a step in the destination direction after arriving
+    pub walk_out: bool,
+    pub lock: Option<Lock>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TransitionStyle {
+    #[default]
+    FadeWhite,
+    FadeBlack,
+    Iris,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TransitionTrigger {
+    /// Stepping onto the entrance tile
+    #[default]
+    Step,
+    /// Walking in the direction while standing on the entrance tile, used for stairs
+    Walk(Direction),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lock {
+    Flag(String),
+    Item(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AnimationPlayback {
+    Loop,
+    Once,
+    Paused,
+}
+
+#[derive(Debug, Clone)]
+pub struct TileAnimation {
+    frames: Vec<IVec2>,         // Positions of each frame in the tileset
+    durations: Vec<Duration>,   // How long each frame should display
+    current_frame: usize,       // Current frame index
+    accumulated_time: Duration, // Time accumulated since last frame change
+    playback: AnimationPlayback,
+}
+
+impl TileAnimation {
+    /// Animation over the given frames, which all display as long, like in LDtk. There has to be
+    /// at least one frame. The loaders reject animations without frames.
+    pub fn new(frames: Vec<IVec2>, frame_duration: Duration) -> Self {
+        let durations = vec![frame_duration; frames.len()];
+        Self::with_durations(frames.into_iter().zip(durations).collect())
+    }
+
+    /// Animation with its own duration for each frame, like in Tiled
+    pub fn with_durations(frames: Vec<(IVec2, Duration)>) -> Self {
+        debug_assert!(
+            !frames.is_empty(),
+            "tile animations need at least one frame"
+        );
+        let (frames, durations) = frames.into_iter().unzip();
+        Self {
+            frames,
+            durations,
+            current_frame: 0,
+            accumulated_time: Duration::ZERO,
+            playback: AnimationPlayback::Loop,
+        }
+    }
+
+    pub fn frames(&self) -> &[IVec2] {
+        &self.frames
+    }
+
+    pub fn update(&mut self, delta_time: Duration) {
+        if self.playback == AnimationPlayback::Paused {
+            return;
+        }
+
+        self.accumulated_time += delta_time;
+        let frame_duration = self.durations[self.current_frame];
+        if self.accumulated_time >= frame_duration {
+            if self.playback == AnimationPlayback::Once
+                && self.current_frame == self.frames.len() - 1
+            {
+                self.playback = AnimationPlayback::Paused;
+                return;
+            }
+
+            self.current_frame = (self.current_frame + 1) % self.frames.len();
+            self.accumulated_time -= frame_duration;
+        }
+    }
+
+    /// Stops the animation on its first frame
+    pub fn pause(&mut self) {
+        self.playback = AnimationPlayback::Paused;
+        self.current_frame = 0;
+        self.accumulated_time = Duration::ZERO;
+    }
+
+    /// Plays the animation from the start and stops on the last frame
+    pub fn play_once(&mut self) {
+        self.playback = AnimationPlayback::Once;
+        self.current_frame = 0;
+        self.accumulated_time = Duration::ZERO;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback != AnimationPlayback::Paused
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.playback == AnimationPlayback::Paused && self.current_frame == self.frames.len() - 1
+    }
+
+    pub fn current_position(&self) -> IVec2 {
+        self.frames[self.current_frame]
+    }
+}
+
+impl TileMap {
+    /// Loads a map from an LDtk project, or from a Tiled map when the path ends in .tmx
+    pub fn load(path: &str) -> Result<Self, TileMapError> {
+        Self::load_with(path, LoadOptions::default())
+    }
+
+    /// Loads a map with the given options. Tiled maps are always loaded upfront.
+    pub fn load_with(path: &str, options: LoadOptions) -> Result<Self, TileMapError> {
+        Self::load_from(&AssetSource::default(), path, options)
+    }
+
+    /// Loads a map with a path relative to the root of an asset source
+    pub fn load_from(
+        source: &AssetSource,
+        path: &str,
+        options: LoadOptions,
+    ) -> Result<Self, TileMapError> {
+        Self::load_with_tilesets(
+            TilesetCache::new(ImageCache::new(source.clone())),
+            path,
+            options,
+        )
+    }
+
+    /// Loads a map from the source of the tileset cache, reusing the tilesets already in it
+    pub fn load_with_tilesets(
+        tilesets: TilesetCache,
+        path: &str,
+        options: LoadOptions,
+    ) -> Result<Self, TileMapError> {
+        if path.ends_with(".tmx") {
+            return tiled::load(tilesets, path);
+        }
+
+        let source = &tilesets.source().clone();
+
+        let json = source.read_to_string(path).map_err(|error| TileMapError::Io {
+            path: source.display_path(path),
+            source: error,
+        })?;
+        let ldtk = Ldtk::from_str(json).map_err(|error| TileMapError::Project {
+            path: source.display_path(path),
+            source: error.into(),
+        })?;
+        let directory = Path::new(path).parent().unwrap_or(Path::new("")).to_path_buf();
+
+        let level_data = ldtk
+            .levels
+            .into_iter()
+            .map(|level| load_external_level(level, source, &directory))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Get initial information from first level
+        let first_level = level_data.first().ok_or(TileMapError::NoLevels)?;
+        let first_tile_layer = layer_instances(first_level)?
+            .iter()
+            .find(|layer| layer.tileset_def_uid.is_some())
+            .ok_or_else(|| TileMapError::NoTileLayers {
+                level: first_level.identifier.clone(),
+            })?;
+
+        let first_tileset = find_tileset(first_level, first_tile_layer, &ldtk.defs)?;
+        let tilesize = first_tileset.tile_grid_size;
+        if tilesize <= 0 {
+            return Err(TileMapError::InvalidTile {
+                level: first_level.identifier.clone(),
+                layer: first_tile_layer.identifier.clone(),
+                reason: format!(
+                    "tileset {} has tiles of {}px",
+                    first_tileset.identifier, tilesize
+                ),
+            });
+        }
+
+        // Outdoor levels are streamed next to each other, offset by whole tiles
+        if let Some(level) = level_data.iter().find(|level| {
+            !is_indoors(level) && (level.world_x % tilesize != 0 || level.world_y % tilesize != 0)
+        }) {
+            return Err(TileMapError::MisalignedLevel {
+                level: level.identifier.clone(),
+                world_x: level.world_x,
+                world_y: level.world_y,
+                tile_size: tilesize,
+            });
+        }
+
+        let player_start = Self::get_player_start(&level_data)?;
+        let entities = Self::load_all_entities(&level_data)?;
+
+        let defs = Arc::new(ldtk.defs);
+        let pending = level_data
+            .into_iter()
+            .map(|data| {
+                let source = LevelSource {
+                    data,
+                    defs: defs.clone(),
+                    directory: directory.clone(),
+                    tilesets: tilesets.clone(),
+                };
+                (source.data.iid.clone(), source)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut tilemap = TileMap {
+            levels: HashMap::new(),
+            tilesize,
+            player_starting_position: player_start.0,
+            current_level_id: player_start.1.clone(),
+            entities,
+            pending,
+            failed: HashMap::new(),
+            prefetcher: (options.lazy && options.prefetch).then(Prefetcher::spawn),
+            tilesets,
+        };
+
+        if !options.lazy {
+            let ids = tilemap.pending.keys().cloned().collect::<Vec<_>>();
+            for id in ids {
+                tilemap.ensure_loaded(&id)?;
+            }
+        }
+        tilemap.change_level(&player_start.1)?;
+
+        Ok(tilemap)
+    }
+
+    fn get_player_start(levels: &[ldtk2::Level]) -> Result<(Vec2, String), TileMapError> {
+        for level in levels {
+            if let Some(layers) = &level.layer_instances {
+                if let Some(entities_layer) =
+                    layers.iter().find(|layer| layer.identifier == "Entities")
+                {
+                    if let Some(player_start) = entities_layer
+                        .entity_instances
+                        .iter()
+                        .find(|entity| entity.identifier == "PlayerStart")
+                    {
+                        let (x, y) = entity_point(level, player_start, "px", &player_start.px)?;
+                        return Ok((Vec2::new(x as f32, y as f32), level.iid.clone()));
+                    }
+                }
+            }
+        }
+        Err(TileMapError::NoPlayerStart)
+    }
+
+    fn load_level(
+        level_data: &ldtk2::Level,
+        defs: &ldtk2::Definitions,
+        directory: &Path,
+        tileset_cache: &TilesetCache,
+    ) -> Result<Level, TileMapError> {
+        let layer_instances = layer_instances(level_data)?;
+        let collision_layer = find_layer(level_data, "Collision")?;
+        let entities_layer = find_layer(level_data, "Entities")?;
+
+        // Collect entrance entities with their grid positions and destination info
+        let mut entrance_transitions = HashMap::new();
+        for entity in &entities_layer.entity_instances {
+            if entity.identifier == "Entrance" {
+                if let Some(field_value) = field_value(entity, "Entity_ref") {
+                    let destination_entity_id = field_value["entityIid"]
+                        .as_str()
+                        .ok_or_else(|| {
+                            invalid_field(
+                                level_data,
+                                entity,
+                                "Entity_ref",
+                                "expected an entity reference",
+                            )
+                        })?
+                        .to_string();
+                    let grid_pos = entity_point(level_data, entity, "__grid", &entity.grid)?;
+                    entrance_transitions.insert(
+                        grid_pos,
+                        read_transition(
+                            destination_entity_id,
+                            |field| field_str(entity, field),
+                            |field| field_bool(entity, field),
+                        ),
+                    );
+                }
+            }
+        }
+
+        // Collect pressure plates with the game flag they control
+        let mut pressure_plates = HashMap::new();
+        for entity in &entities_layer.entity_instances {
+            if entity.identifier == "PressurePlate" {
+                if let Some(flag) = field_str(entity, "Flag") {
+                    let grid_pos = entity_point(level_data, entity, "__grid", &entity.grid)?;
+                    pressure_plates.insert(grid_pos, flag.to_string());
+                }
+            }
+        }
+
+        check_grid_size(level_data, collision_layer)?;
+        let collision_layer_definition =
+            defs.layers.iter().find(|def| def.uid == collision_layer.layer_def_uid);
+
+        let cell_count = (collision_layer.c_wid * collision_layer.c_hei).max(0) as usize;
+        if collision_layer.int_grid_csv.len() < cell_count {
+            return Err(TileMapError::InvalidTile {
+                level: level_data.identifier.clone(),
+                layer: collision_layer.identifier.clone(),
+                reason: format!(
+                    "expected {} IntGrid values but found {}",
+                    cell_count,
+                    collision_layer.int_grid_csv.len()
+                ),
+            });
+        }
+
+        // Load gameplay data for each cell
+        let tiles = TileGrid::from_fn(collision_layer.c_wid, collision_layer.c_hei, |cell| {
+            let (grid_x, grid_y) = cell;
+            let grid_index = (grid_y * collision_layer.c_wid + grid_x) as usize;
+            let terrain = Terrain::from_int_grid_value(
+                collision_layer.int_grid_csv[grid_index],
+                collision_layer_definition,
+            );
+
+            TileData {
+                position: IVec2::new(
+                    grid_x * collision_layer.grid_size,
+                    grid_y * collision_layer.grid_size,
+                ),
+                traversable: terrain.is_walkable(),
+                terrain,
+                transition: entrance_transitions.remove(&cell),
+            }
+        });
+
+        // Load tile layers, LDtk lists them from top to bottom. Tiles layers are painted by hand,
+        // AutoLayer and IntGrid layers get their tiles from auto-layer rules.
+        let mut tilesets = HashMap::new();
+        let mut layers = Vec::new();
+        for layer in layer_instances.iter().rev() {
+            if layer.grid_tiles.is_empty() && layer.auto_layer_tiles.is_empty() {
+                continue;
+            }
+
+            let tileset = find_tileset(level_data, layer, defs)?;
+
+            if let Entry::Vacant(entry) = tilesets.entry(tileset.uid) {
+                entry.insert(load_tileset(tileset, directory, tileset_cache)?);
+            }
+
+            layers.push(Self::load_layer(level_data, layer, tileset)?);
+        }
+
+        // Tilesets used to display entities, e.g. pushables
+        for entity in &entities_layer.entity_instances {
+            let Some(tile) = &entity.tile else {
+                continue;
+            };
+
+            let tileset =
+                defs.tilesets.iter().find(|t| t.uid == tile.tileset_uid && t.rel_path.is_some());
+            if let (Some(tileset), Entry::Vacant(entry)) =
+                (tileset, tilesets.entry(tile.tileset_uid))
+            {
+                entry.insert(load_tileset(tileset, directory, tileset_cache)?);
+            }
+        }
+
+        let mut level = Level {
+            identifier: level_data.identifier.clone(),
+            tiles,
+            layers,
+            tilesets,
+            pressure_plates,
+            indoors: is_indoors(level_data),
+            world_position: IVec2::new(level_data.world_x, level_data.world_y),
+            pixel_width: level_data.px_wid,
+            pixel_height: level_data.px_hei,
+            neighbours: level_data
+                .neighbours
+                .iter()
+                .map(|neighbour| neighbour.level_iid.clone())
+                .collect(),
+        };
+
+        level.close_doors();
+
+        Ok(level)
+    }
+
+    fn load_layer(
+        level_data: &ldtk2::Level,
+        layer: &ldtk2::LayerInstance,
+        tileset: &ldtk2::TilesetDefinition,
+    ) -> Result<TileLayer, TileMapError> {
+        check_grid_size(level_data, layer)?;
+        let mut tiles: TileGrid<Vec<LayerTile>> = TileGrid::new(layer.c_wid, layer.c_hei);
+        let invalid_tile = |reason: String| TileMapError::InvalidTile {
+            level: level_data.identifier.clone(),
+            layer: layer.identifier.clone(),
+            reason,
+        };
+
+        // Auto-layer tiles are listed in the order the rules painted them, which is also the order
+        // they stack in
+        for tile in layer.grid_tiles.iter().chain(&layer.auto_layer_tiles) {
+            let (Some(px), Some(src)) = (point(&tile.px), point(&tile.src)) else {
+                return Err(invalid_tile(format!(
+                    "tile {} is missing its pixel or source coordinates",
+                    tile.t
+                )));
+            };
+            let tile_custom_data = tileset.custom_data.iter().find(|t| t.tile_id == tile.t);
+
+            let cell = (
+                px.0.div_euclid(layer.grid_size),
+                px.1.div_euclid(layer.grid_size),
+            );
+            let stack = tiles
+                .get_mut(cell)
+                .ok_or_else(|| invalid_tile(format!("tile {} is outside the layer", tile.t)))?;
+            stack.push(LayerTile {
+                tileset_position: IVec2::new(src.0, src.1),
+                position: IVec2::new(px.0, px.1),
+                animation: read_animation_data(tile_custom_data, tileset)?,
+                // Bit 0 of f is the X flip and bit 1 the Y flip
+                flip_x: tile.f & 1 != 0,
+                flip_y: tile.f & 2 != 0,
+                alpha: tile.a as f32,
+            });
+        }
+
+        Ok(TileLayer::new(
+            layer.identifier.clone(),
+            tileset.uid,
+            layer.opacity as f32,
+            IVec2::new(layer.px_total_offset_x, layer.px_total_offset_y),
+            tiles,
+        ))
+    }
+
+    fn load_all_entities(
+        levels: &[ldtk2::Level],
+    ) -> Result<HashMap<EntityId, EntityInstance>, TileMapError> {
+        let mut entities = HashMap::new();
+
+        for level in levels {
+            let entities_layer = find_layer(level, "Entities")?;
+
+            for entity in &entities_layer.entity_instances {
+                let (x, y) = entity_point(level, entity, "px", &entity.px)?;
+                let entity_instance = EntityInstance {
+                    identifier: entity.identifier.clone(),
+                    position: Vec2::new(x as f32, y as f32),
+                    level_id: level.iid.clone(),
+                    direction: get_direction_for_destination_entity(level, entity)?,
+                    tile: entity.tile.as_ref().map(|tile| EntityTile {
+                        tileset_id: tile.tileset_uid,
+                        tileset_position: IVec2::new(tile.x, tile.y),
+                    }),
+                };
+
+                entities.insert(entity.iid.clone(), entity_instance);
+            }
+        }
+
+        Ok(entities)
+    }
+
+    /// Draws tilesets packed into the atlas from its pages, in loaded levels and in levels that are
+    /// loaded later
+    pub fn use_atlas(&mut self, atlas: &Atlas) {
+        self.tilesets.use_atlas(atlas);
+        for level in self.levels.values_mut() {
+            for tileset in level.tilesets.values_mut() {
+                if let Some(packed) = self.tilesets.get(tileset.image.path()) {
+                    *tileset = packed;
+                }
+            }
+        }
+    }
+
+    pub fn levels(&self) -> impl Iterator<Item = (&LevelId, &Level)> {
+        self.levels.iter()
+    }
+
+    pub fn get_level(&self, id: &str) -> Option<&Level> {
+        self.levels.get(id)
+    }
+
+    pub fn current_level(&self) -> &Level {
+        self.levels.get(&self.current_level_id).unwrap()
+    }
+
+    pub fn current_level_mut(&mut self) -> &mut Level {
+        self.levels.get_mut(&self.current_level_id).unwrap()
+    }
+
+    pub fn current_level_id(&self) -> &str {
+        &self.current_level_id
+    }
+
+    /// Makes a level current, building it and its streamed neighbours first if they were loaded
+    /// lazily
+    pub fn change_level(&mut self, destination_level_id: &str) -> Result<(), TileMapError> {
+        self.ensure_loaded(destination_level_id)?;
+        self.current_level_id = destination_level_id.to_string();
+
+        // Outdoor neighbours are drawn right away, so they can't wait for the prefetch thread
+        if !self.current_level().indoors {
+            for neighbour in self.current_level().neighbours.clone() {
+                let indoors =
+                    self.pending.get(&neighbour).is_some_and(|source| is_indoors(&source.data));
+                if !indoors {
+                    self.ensure_loaded(&neighbour)?;
+                }
+            }
+        }
+
+        self.prefetch_reachable_levels();
+        Ok(())
+    }
+
+    /// Builds a level that hasn't been built yet, waiting for the prefetch thread if it is
+    /// already building it
+    fn ensure_loaded(&mut self, id: &str) -> Result<(), TileMapError> {
+        self.collect_prefetched();
+
+        while !self.levels.contains_key(id)
+            && self.prefetcher.as_ref().is_some_and(|prefetcher| prefetcher.is_in_flight(id))
+        {
+            let Some((built_id, level)) = self.prefetcher.as_mut().and_then(Prefetcher::wait)
+            else {
+                break;
+            };
+            self.insert_built(built_id, level);
+        }
+
+        if self.levels.contains_key(id) {
+            return Ok(());
+        }
+        if let Some(error) = self.failed.remove(id) {
+            return Err(error);
+        }
+
+        let source = self.pending.remove(id).ok_or_else(|| TileMapError::UnknownLevel {
+            level: id.to_string(),
+        })?;
+        let level = source.build()?;
+        self.levels.insert(id.to_string(), level);
+        Ok(())
+    }
+
+    fn collect_prefetched(&mut self) {
+        let built = self.prefetcher.as_mut().map(Prefetcher::finished).unwrap_or_default();
+        for (id, level) in built {
+            self.insert_built(id, level);
+        }
+    }
+
+    fn insert_built(&mut self, id: LevelId, level: Result<Level, TileMapError>) {
+        match level {
+            Ok(level) => {
+                self.levels.insert(id, level);
+            }
+            Err(error) => {
+                self.failed.insert(id, error);
+            }
+        }
+    }
+
+    /// Queues the levels the player can reach from the current level on the prefetch thread
+    fn prefetch_reachable_levels(&mut self) {
+        let Some(prefetcher) = &mut self.prefetcher else {
+            return;
+        };
+
+        let current_level = self.levels.get(&self.current_level_id);
+        let destinations = current_level
+            .into_iter()
+            .flat_map(|level| level.tiles.iter())
+            .filter_map(|(_, tile)| tile.transition.as_ref())
+            .filter_map(|transition| self.entities.get(&transition.destination))
+            .map(|destination| destination.level_id.clone());
+        let reachable = current_level
+            .into_iter()
+            .flat_map(|level| level.neighbours.iter().cloned())
+            .chain(destinations)
+            .collect::<Vec<_>>();
+
+        for id in reachable {
+            let Some(source) = self.pending.remove(&id) else {
+                continue;
+            };
+            if let Some(source) = prefetcher.request(id.clone(), source) {
+                // The thread is gone, the level is built when it is entered instead
+                self.pending.insert(id, source);
+            }
+        }
+    }
+
+    pub fn tilesize(&self) -> i64 {
+        self.tilesize
+    }
+
+    /// Outdoor levels next to the current outdoor level, which are drawn and collided against so
+    /// the player can walk between them without a transition. Comes with the offset in pixels from
+    /// the current level to the neighbour.
+    pub fn streamed_neighbours(&self) -> impl Iterator<Item = (&LevelId, &Level, IVec2)> {
+        let current_level = self.current_level();
+        current_level
+            .neighbours
+            .iter()
+            .filter(|_| !current_level.indoors)
+            .filter_map(|id| self.levels.get(id).map(|level| (id, level)))
+            .filter(|(_, level)| !level.indoors)
+            .map(|(id, level)| {
+                (
+                    id,
+                    level,
+                    level.world_position - current_level.world_position,
+                )
+            })
+    }
+
+    /// Top left and bottom right corners of the area a view centred on the point can show, in
+    /// pixels relative to the current level. That is the level the point is in, joined by the
+    /// streamed neighbours lined up with it, so the view scrolls into them without showing the
+    /// empty space next to levels of different sizes.
+    pub fn pixel_bounds(&self, point: Vec2) -> (IVec2, IVec2) {
+        let current_level = self.current_level();
+        let current = (
+            IVec2::ZERO,
+            IVec2::new(current_level.pixel_width, current_level.pixel_height),
+        );
+        let levels = std::iter::once(current)
+            .chain(self.streamed_neighbours().map(|(_, level, offset)| {
+                (
+                    offset,
+                    offset + IVec2::new(level.pixel_width, level.pixel_height),
+                )
+            }))
+            .collect::<Vec<_>>();
+
+        let within = |value: f32, min: i64, max: i64| min as f32 <= value && value < max as f32;
+        let (mut min, mut max) = levels
+            .iter()
+            .find(|(min, max)| within(point.x, min.x, max.x) && within(point.y, min.y, max.y))
+            .copied()
+            .unwrap_or(current);
+
+        // A neighbour joins when it touches the area and covers all of it the other way. It can
+        // line up with a neighbour of its own, so this repeats until nothing else joins.
+        let mut joined = true;
+        while joined {
+            let area = (min, max);
+            for &(level_min, level_max) in &levels {
+                let beside = level_min.y <= min.y
+                    && level_max.y >= max.y
+                    && (level_max.x == min.x || level_min.x == max.x);
+                let above_or_below = level_min.x <= min.x
+                    && level_max.x >= max.x
+                    && (level_max.y == min.y || level_min.y == max.y);
+                if beside {
+                    min.x = min.x.min(level_min.x);
+                    max.x = max.x.max(level_max.x);
+                } else if above_or_below {
+                    min.y = min.y.min(level_min.y);
+                    max.y = max.y.max(level_max.y);
+                }
+            }
+            joined = (min, max) != area;
+        }
+        (min, max)
+    }
+
+    /// Looks up a tile in the current level, falling back to the streamed neighbours
+    pub fn tile_at(&self, tile: (i64, i64)) -> Option<&TileData> {
+        if let Some(tile_data) = self.current_level().tiles.get(tile) {
+            return Some(tile_data);
+        }
+
+        self.streamed_neighbours()
+            .find_map(|(_, level, offset)| level.tiles.get(self.offset_tile(tile, offset)))
+    }
+
+    /// Finds the streamed neighbour that has a tile at the given tile of the current level
+    pub fn neighbour_at(&self, tile: (i64, i64)) -> Option<(LevelId, IVec2)> {
+        self.streamed_neighbours()
+            .find(|(_, level, offset)| level.tiles.contains(self.offset_tile(tile, *offset)))
+            .map(|(id, _, offset)| (id.clone(), offset))
+    }
+
+    /// Ids of the current level and its streamed neighbours
+    pub fn levels_in_view(&self) -> Vec<LevelId> {
+        let mut ids = vec![self.current_level_id.clone()];
+        ids.extend(self.streamed_neighbours().map(|(id, _, _)| id.clone()));
+        ids
+    }
+
+    pub fn get_level_mut(&mut self, id: &str) -> Option<&mut Level> {
+        self.levels.get_mut(id)
+    }
+
+    /// Tile of a streamed neighbour at a tile of the current level. The loaders make sure the tile
+    /// size is at least a pixel and outdoor levels are on the tile grid.
+    fn offset_tile(&self, tile: (i64, i64), offset: IVec2) -> (i64, i64) {
+        (
+            tile.0 - offset.x / self.tilesize,
+            tile.1 - offset.y / self.tilesize,
+        )
+    }
+}
+
+fn layer_instances(level: &ldtk2::Level) -> Result<&[ldtk2::LayerInstance], TileMapError> {
+    level.layer_instances.as_deref().ok_or_else(|| TileMapError::MissingLayers {
+        level: level.identifier.clone(),
+    })
+}
+
+fn find_layer<'a>(
+    level: &'a ldtk2::Level,
+    identifier: &str,
+) -> Result<&'a ldtk2::LayerInstance, TileMapError> {
+    layer_instances(level)?.iter().find(|layer| layer.identifier == identifier).ok_or_else(|| {
+        TileMapError::MissingLayer {
+            level: level.identifier.clone(),
+            layer: identifier.to_string(),
+        }
+    })
+}
+
+/// Cells are found by dividing by the grid size, so it has to be at least a pixel
+fn check_grid_size(level: &ldtk2::Level, layer: &ldtk2::LayerInstance) -> Result<(), TileMapError> {
+    if layer.grid_size > 0 {
+        return Ok(());
+    }
+    Err(TileMapError::InvalidTile {
+        level: level.identifier.clone(),
+        layer: layer.identifier.clone(),
+        reason: format!("grid size is {}px", layer.grid_size),
+    })
+}
+
+fn find_tileset<'a>(
+    level: &ldtk2::Level,
+    layer: &ldtk2::LayerInstance,
+    defs: &'a ldtk2::Definitions,
+) -> Result<&'a ldtk2::TilesetDefinition, TileMapError> {
+    let tileset_id = layer.override_tileset_uid.or(layer.tileset_def_uid);
+    defs.tilesets.iter().find(|tileset| Some(tileset.uid) == tileset_id).ok_or_else(|| {
+        TileMapError::MissingTileset {
+            level: level.identifier.clone(),
+            layer: layer.identifier.clone(),
+            tileset_id,
+        }
+    })
+}
+
+fn load_tileset(
+    tileset: &ldtk2::TilesetDefinition,
+    directory: &Path,
+    cache: &TilesetCache,
+) -> Result<Arc<Tileset>, TileMapError> {
+    let rel_path = tileset.rel_path.as_ref().ok_or_else(|| TileMapError::MissingTilesetImage {
+        tileset: tileset.identifier.clone(),
+    })?;
+    cache.load(&directory.join(rel_path))
+}
+
+fn is_indoors(level_data: &ldtk2::Level) -> bool {
+    level_data.field_instances.iter().any(|field| {
+        field.identifier == "indoors"
+            && field.value.as_ref().and_then(|value| value.as_bool()).unwrap_or(false)
+    })
+}
+
+/// Levels saved in separate files only have their layers in the .ldtkl file next to the project
+fn load_external_level(
+    level: ldtk2::Level,
+    source: &AssetSource,
+    directory: &Path,
+) -> Result<ldtk2::Level, TileMapError> {
+    let Some(rel_path) =
+        level.external_rel_path.as_ref().filter(|_| level.layer_instances.is_none())
+    else {
+        return Ok(level);
+    };
+
+    let path = directory.join(rel_path);
+    let json = source.read_to_string(&path).map_err(|error| TileMapError::Io {
+        path: source.display_path(&path),
+        source: error,
+    })?;
+    serde_json::from_str(&json).map_err(|error| TileMapError::Project {
+        path: source.display_path(&path),
+        source: error.into(),
+    })
+}
+
+fn point(values: &[i64]) -> Option<(i64, i64)> {
+    match values {
+        [x, y, ..] => Some((*x, *y)),
+        _ => None,
+    }
+}
+
+fn entity_point(
+    level: &ldtk2::Level,
+    entity: &ldtk2::EntityInstance,
+    field: &str,
+    values: &[i64],
+) -> Result<(i64, i64), TileMapError> {
+    point(values).ok_or_else(|| invalid_field(level, entity, field, "expected two coordinates"))
+}
+
+fn invalid_field(
+    level: &ldtk2::Level,
+    entity: &ldtk2::EntityInstance,
+    field: &str,
+    reason: &str,
+) -> TileMapError {
+    TileMapError::InvalidField {
+        level: level.identifier.clone(),
+        entity_iid: entity.iid.clone(),
+        field: field.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn field_value<'a>(
+    entity: &'a ldtk2::EntityInstance,
+    identifier: &str,
+) -> Option<&'a serde_json::Value> {
+    entity
+        .field_instances
+        .iter()
+        .find(|field| field.identifier == identifier)
+        .and_then(|field| field.value.as_ref())
+        .filter(|value| !value.is_null())
+}
+
+fn field_str<'a>(entity: &'a ldtk2::EntityInstance, identifier: &str) -> Option<&'a str> {
+    field_value(entity, identifier).and_then(|value| value.as_str())
+}
+
+fn field_bool(entity: &ldtk2::EntityInstance, identifier: &str) -> bool {
+    field_value(entity, identifier).and_then(|value| value.as_bool()).unwrap_or(false)
+}
+
+/// Builds the transition of an Entrance entity from its fields, shared by the LDtk and Tiled
+/// loaders
+fn read_transition<'a>(
+    destination: String,
+    field_str: impl Fn(&str) -> Option<&'a str>,
+    field_bool: impl Fn(&str) -> bool,
+) -> Transition {
+    Transition {
+        destination,
+        style: match field_str("Transition") {
+            Some("FadeBlack") => TransitionStyle::FadeBlack,
+            Some("Iris") => TransitionStyle::Iris,
+            _ => TransitionStyle::FadeWhite,
+        },
+        trigger: field_str("Stairs")
+            .and_then(|direction| Direction::from_str(direction).ok())
+            .map_or(TransitionTrigger::Step, TransitionTrigger::Walk),
+        door: field_bool("Door"),
+        walk_out: field_bool("Walk_out"),
+        lock: field_str("Required_flag")
+            .map(|flag| Lock::Flag(flag.to_string()))
+            .or_else(|| field_str("Required_item").map(|item| Lock::Item(item.to_string()))),
+    }
+}
+
+// TODO: refactor so that not all EntityInstace has direction field when it only applies to
+// Destination entity
+/// A Destination without a direction keeps the player's facing, but one that is set must be valid
+fn get_direction_for_destination_entity(
+    level: &ldtk2::Level,
+    entity: &ldtk2::EntityInstance,
+) -> Result<Option<Direction>, TileMapError> {
+    if entity.identifier != "Destination" {
+        return Ok(None);
+    }
+
+    let Some(value) = field_value(entity, "Direction") else {
+        return Ok(None);
+    };
+
+    let direction = value
+        .as_str()
+        .ok_or_else(|| invalid_field(level, entity, "Direction", "expected a string"))?;
+    Direction::from_str(direction)
+        .map(Some)
+        .map_err(|reason| invalid_field(level, entity, "Direction", &reason))
+}
+
+/// tile custom data must contain animation data on this format:
+/// animationTiles:1,2,3 (these are the tile ids in the tileset)
+/// frameTime:100 (time in ms for each frame)
+fn read_animation_data(
+    tile_custom_data: Option<&ldtk2::TileCustomMetadata>,
+    tileset: &ldtk2::TilesetDefinition,
+) -> Result<Option<TileAnimation>, TileMapError> {
+    let Some(tile_custom_data) = tile_custom_data else {
+        return Ok(None);
+    };
+    if !tile_custom_data.data.starts_with("animationTiles") {
+        return Ok(None);
+    }
+
+    let invalid = |reason: String| TileMapError::InvalidAnimation {
+        tileset: tileset.identifier.clone(),
+        tile_id: tile_custom_data.tile_id,
+        reason,
+    };
+
+    // Frames are found by dividing their tile id by the tileset's columns
+    if tileset.c_wid <= 0 || tileset.tile_grid_size <= 0 {
+        return Err(invalid(format!(
+            "tileset has {} columns of {}px tiles",
+            tileset.c_wid, tileset.tile_grid_size
+        )));
+    }
+
+    let mut custom_data_lines = tile_custom_data.data.lines();
+    let animation_tiles = custom_data_lines
+        .next()
+        .and_then(|line| line.split_once(':'))
+        .ok_or_else(|| invalid("missing animationTiles".to_string()))?
+        .1;
+    if animation_tiles.trim().is_empty() {
+        return Err(invalid("animation has no frames".to_string()));
+    }
+    let animation_frames = animation_tiles
+        .split(',')
+        .map(|id| {
+            let id = id.trim();
+            id.parse::<u32>()
+                .map(|id| tile_id_to_position(id, tileset.c_wid, tileset.tile_grid_size))
+                .map_err(|_| invalid(format!("tile id {:?} is not a number", id)))
+        })
+        .collect::<Result<Vec<IVec2>, TileMapError>>()?;
+
+    let frame_time = custom_data_lines
+        .next()
+        .and_then(|line| line.split_once(':'))
+        .ok_or_else(|| invalid("missing frameTime".to_string()))?
+        .1
+        .trim();
+    let frame_time = frame_time
+        .parse::<u64>()
+        .map_err(|_| invalid(format!("frame time {:?} is not a number", frame_time)))?;
+
+    Ok(Some(TileAnimation::new(
+        animation_frames,
+        Duration::from_millis(frame_time),
+    )))
+}
+
+fn tile_id_to_position(id: u32, tileset_width: i64, tilesize: i64) -> IVec2 {
+    let x = id as i64 % tileset_width * tilesize;
+    let y = id as i64 / tileset_width * tilesize;
+    IVec2::new(x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, SMALL};
+
+    #[test]
+    fn animation_without_frames_is_rejected() {
+        let error = test_fixtures::load_modified(SMALL, |project| {
+            project["defs"]["tilesets"][0]["customData"][0]["data"] =
+                "animationTiles:\nframeTime:100".into();
+        })
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            TileMapError::InvalidAnimation { tile_id: 3, .. }
+        ));
+    }
+
+    #[test]
+    fn doors_are_closed_when_loaded() {
+        let mut tilemap = test_fixtures::load(SMALL);
+        let door = tilemap.current_level_mut().animation_at_mut((3, 3)).unwrap();
+
+        assert!(!door.is_playing());
+        assert_eq!(door.current_position(), IVec2::new(16, 16));
+    }
+
+    #[test]
+    fn door_played_once_stops_on_its_last_frame() {
+        let mut tilemap = test_fixtures::load(SMALL);
+        let door = tilemap.current_level_mut().animation_at_mut((3, 3)).unwrap();
+
+        door.play_once();
+        door.update(Duration::from_millis(100));
+        assert!(door.is_playing());
+        assert!(!door.is_finished());
+
+        door.update(Duration::from_millis(100));
+        assert!(door.is_finished());
+        assert_eq!(door.current_position(), IVec2::new(0, 16));
+    }
+
+    #[test]
+    fn outdoor_level_off_the_tile_grid_is_rejected() {
+        let error = test_fixtures::load_modified(SMALL, |project| {
+            project["levels"][2]["worldX"] = 70.into();
+        })
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            TileMapError::MisalignedLevel { world_x: 70, .. }
+        ));
+    }
+
+    #[test]
+    fn tile_layers_are_loaded_bottom_to_top_with_their_placement() {
+        let tilemap = test_fixtures::load(SMALL);
+        let level = tilemap.current_level();
+
+        let layers = level
+            .layers
+            .iter()
+            .map(|layer| (layer.identifier.as_str(), layer.placement))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            layers,
+            [
+                ("Tiles", LayerPlacement::BelowSprites),
+                ("Grass", LayerPlacement::BelowSprites),
+                ("Above_Roofs", LayerPlacement::AboveSprites),
+            ]
+        );
+
+        let roofs = level.layers_with_placement(LayerPlacement::AboveSprites).collect::<Vec<_>>();
+        assert_eq!(roofs.len(), 1);
+        let roof = &roofs[0].tiles.get((1, 3)).unwrap()[0];
+        assert_eq!(roof.position, IVec2::new(16, 48));
+        assert_eq!(roof.tileset_position, IVec2::new(16, 0));
+        assert!(roofs[0].tiles.get((0, 0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn auto_layer_tiles_keep_their_order_flips_and_alpha() {
+        let tilemap = test_fixtures::load(SMALL);
+        let grass = tilemap.current_level().layers.iter().find(|layer| layer.identifier == "Grass");
+        let grass = grass.unwrap();
+
+        let stack = grass.tiles.get((0, 2)).unwrap();
+        let stacked = stack
+            .iter()
+            .map(|tile| (tile.tileset_position, tile.flip_x, tile.flip_y, tile.alpha))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stacked,
+            [
+                (IVec2::new(0, 0), false, false, 1.0),
+                (IVec2::new(16, 0), true, false, 0.5),
+            ]
+        );
+
+        let flipped_y = &grass.tiles.get((1, 2)).unwrap()[0];
+        assert!(!flipped_y.flip_x && flipped_y.flip_y);
+
+        let flipped_both = &grass.tiles.get((2, 2)).unwrap()[0];
+        assert!(flipped_both.flip_x && flipped_both.flip_y);
+        assert_eq!(flipped_both.alpha, 0.25);
+        assert_eq!(flipped_both.position, IVec2::new(32, 32));
+    }
+
+    #[test]
+    fn layer_with_a_grid_size_of_zero_is_rejected() {
+        let error = test_fixtures::load_modified(SMALL, |project| {
+            let layers = project["levels"][0]["layerInstances"].as_array_mut().unwrap();
+            let tiles = layers.iter_mut().find(|layer| layer["__identifier"] == "Grass").unwrap();
+            tiles["__gridSize"] = 0.into();
+        })
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            TileMapError::InvalidTile { ref level, ref layer, .. } if level == "Start" && layer == "Grass"
+        ));
+    }
+
+    #[test]
+    fn collision_layer_with_a_grid_size_of_zero_is_rejected() {
+        let error = test_fixtures::load_modified(SMALL, |project| {
+            let layers = project["levels"][1]["layerInstances"].as_array_mut().unwrap();
+            let collision =
+                layers.iter_mut().find(|layer| layer["__identifier"] == "Collision").unwrap();
+            collision["__gridSize"] = 0.into();
+        })
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            TileMapError::InvalidTile { ref layer, .. } if layer == "Collision"
+        ));
+    }
+
+    #[test]
+    fn tileset_with_tiles_of_zero_pixels_is_rejected() {
+        let error = test_fixtures::load_modified(SMALL, |project| {
+            project["defs"]["tilesets"][0]["tileGridSize"] = 0.into();
+        })
+        .unwrap_err();
+
+        assert!(matches!(error, TileMapError::InvalidTile { .. }));
+    }
+
+    #[test]
+    fn animated_tileset_without_columns_is_rejected() {
+        let error = test_fixtures::load_modified(SMALL, |project| {
+            project["defs"]["tilesets"][0]["__cWid"] = 0.into();
+        })
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            TileMapError::InvalidAnimation { tile_id: 3, .. }
+        ));
+    }
+
+    #[test]
+    fn bounds_join_the_neighbours_lined_up_with_the_level() {
+        // East is as tall as Start, so the view scrolls from one into the other
+        let tilemap = test_fixtures::load(SMALL);
+        assert_eq!(
+            tilemap.pixel_bounds(Vec2::new(32.0, 32.0)),
+            (IVec2::new(0, 0), IVec2::new(112, 64))
+        );
+
+        // Moved down half of Start, East would leave space under Start and above itself
+        let tilemap = test_fixtures::load_modified(SMALL, |project| {
+            project["levels"][2]["worldY"] = 32.into();
+        })
+        .unwrap();
+        assert_eq!(
+            tilemap.pixel_bounds(Vec2::new(32.0, 32.0)),
+            (IVec2::new(0, 0), IVec2::new(64, 64))
+        );
+        assert_eq!(
+            tilemap.pixel_bounds(Vec2::new(80.0, 48.0)),
+            (IVec2::new(64, 32), IVec2::new(112, 96))
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
+
+use super::{cache::TilesetCache, Level, LevelId, TileMap, TileMapError};
+
+/// Everything needed to build a level that hasn't been loaded yet
+#[derive(Debug)]
+pub(super) struct LevelSource {
+    pub data: ldtk2::Level,
+    pub defs: Arc<ldtk2::Definitions>,
+    /// Directory of the LDtk project, which tileset paths are relative to
+    pub directory: PathBuf,
+    pub tilesets: TilesetCache,
+}
+
+impl LevelSource {
+    pub fn build(&self) -> Result<Level, TileMapError> {
+        TileMap::load_level(&self.data, &self.defs, &self.directory, &self.tilesets)
+    }
+}
+
+type Built = (LevelId, Result<Level, TileMapError>);
+
+/// Builds levels on a background thread, ahead of the player entering them
+#[derive(Debug)]
+pub(super) struct Prefetcher {
+    jobs: Sender<(LevelId, LevelSource)>,
+    results: Receiver<Built>,
+    in_flight: HashSet<LevelId>,
+}
+
+impl Prefetcher {
+    pub fn spawn() -> Self {
+        let (jobs, job_receiver) = channel::<(LevelId, LevelSource)>();
+        let (result_sender, results) = channel();
+
+        // The thread stops once the tile map, and with it the job sender, is dropped
+        thread::spawn(move || {
+            for (id, source) in job_receiver {
+                let level = source.build();
+                if result_sender.send((id, level)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            jobs,
+            results,
+            in_flight: HashSet::new(),
+        }
+    }
+
+    /// Queues a level to be built. The source is handed back if the thread is gone.
+    pub fn request(&mut self, id: LevelId, source: LevelSource) -> Option<LevelSource> {
+        match self.jobs.send((id.clone(), source)) {
+            Ok(()) => {
+                self.in_flight.insert(id);
+                None
+            }
+            Err(error) => Some(error.0 .1),
+        }
+    }
+
+    pub fn is_in_flight(&self, id: &str) -> bool {
+        self.in_flight.contains(id)
+    }
+
+    /// Levels that finished building since the last call, without waiting
+    pub fn finished(&mut self) -> Vec<Built> {
+        let built = self.results.try_iter().collect::<Vec<_>>();
+        for (id, _) in &built {
+            self.in_flight.remove(id);
+        }
+        built
+    }
+
+    /// Waits for the next level to finish building, None if nothing is in flight
+    pub fn wait(&mut self) -> Option<Built> {
+        if self.in_flight.is_empty() {
+            return None;
+        }
+
+        let built = self.results.recv().ok()?;
+        self.in_flight.remove(&built.0);
+        Some(built)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_fixtures::{self, SMALL},
+        tile::LoadOptions,
+    };
+
+    const HOUSE: &str = "house";
+
+    fn lazy(prefetch: bool) -> LoadOptions {
+        LoadOptions {
+            lazy: true,
+            prefetch,
+        }
+    }
+
+    #[test]
+    fn lazy_level_is_built_when_it_is_first_entered() {
+        let mut tilemap = TileMap::load_from(&test_fixtures::source(), SMALL, lazy(false)).unwrap();
+
+        // East is streamed next to Start, so it is built right away
+        assert!(tilemap.get_level("east").is_some());
+        assert!(tilemap.get_level(HOUSE).is_none());
+
+        tilemap.change_level(HOUSE).unwrap();
+
+        assert!(tilemap.get_level(HOUSE).is_some());
+        assert!(tilemap.pending.is_empty());
+    }
+
+    #[test]
+    fn prefetched_level_is_picked_up_when_it_is_entered() {
+        let mut tilemap = TileMap::load_from(&test_fixtures::source(), SMALL, lazy(true)).unwrap();
+
+        // The door in Start leads to the house, so it is handed to the prefetch thread
+        assert!(!tilemap.pending.contains_key(HOUSE));
+        assert!(tilemap.prefetcher.as_ref().unwrap().is_in_flight(HOUSE));
+
+        tilemap.change_level(HOUSE).unwrap();
+
+        assert!(tilemap.get_level(HOUSE).is_some());
+        assert!(!tilemap.prefetcher.as_ref().unwrap().is_in_flight(HOUSE));
+    }
+
+    #[test]
+    fn level_that_failed_to_prefetch_is_reported_when_it_is_entered() {
+        let source = test_fixtures::modified(SMALL, |project| {
+            let layers = project["levels"][1]["layerInstances"].as_array_mut().unwrap();
+            let collision =
+                layers.iter_mut().find(|layer| layer["__identifier"] == "Collision").unwrap();
+            collision["__gridSize"] = 0.into();
+        });
+        let mut tilemap = TileMap::load_from(&source, SMALL, lazy(true)).unwrap();
+
+        let error = tilemap.change_level(HOUSE).unwrap_err();
+
+        assert!(matches!(
+            error,
+            TileMapError::InvalidTile { ref level, .. } if level == "House"
+        ));
+        assert_eq!(tilemap.current_level().identifier, "Start");
+    }
+}
@@ -0,0 +1,759 @@
+//! Loads Tiled maps (.tmx) and their tilesets (.tsx) into the same structures as LDtk projects.
+//!
+//! Maps follow the same conventions as the LDtk project:
+//! - A tile layer named `Collision` holds the gameplay grid. Empty cells are ground and tiles are
+//!   walls, unless the tile's class names a terrain such as `WaterBoundary`, `Ice` or
+//!   `Conveyor_Up`.
+//! - Other tile layers are drawn, the ones named `Above...` on top of sprites.
+//! - Objects are entities identified by their class, with their custom properties as fields.
+//! - `Entity_ref` on an `Entrance` is either an object property pointing into the same map, or a
+//!   string like `house.tmx#3` pointing at object 3 of another map, which is then loaded too.
+//! - The map property `indoors` marks indoor levels.
+//!
+//! Every map is a level named after its file. Tiled worlds are not supported, so levels have no
+//! neighbours.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use xml::reader::{EventReader, XmlEvent};
+
+use super::{
+    read_transition, EntityId, EntityInstance, EntityTile, LayerTile, Level, LevelId, Terrain,
+    TileAnimation, TileData, TileGrid, TileLayer, TileMap, TileMapError, Tileset, TilesetCache,
+    TilesetId,
+};
+use crate::{assets::AssetSource, ivec2::IVec2, movement_util::Direction, vec2::Vec2};
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const ROTATED_HEXAGONAL: u32 = 0x1000_0000;
+
+/// Loads the map at the path along with every map its entrances lead to
+pub(super) fn load(cache: TilesetCache, path: &str) -> Result<TileMap, TileMapError> {
+    let source = &cache.source().clone();
+    let mut pending = vec![PathBuf::from(path)];
+    let mut levels = HashMap::new();
+    let mut entities = HashMap::new();
+    let mut tilesize = None;
+    let mut player_start = None;
+
+    while let Some(path) = pending.pop() {
+        let level_id = map_level_id(&path);
+        if levels.contains_key(&level_id) {
+            continue;
+        }
+
+        let map = load_map(source, &path, &level_id, &cache)?;
+        tilesize.get_or_insert(map.tilesize);
+        if player_start.is_none() {
+            player_start = map.player_start.map(|position| (position, level_id.clone()));
+        }
+
+        pending.extend(map.referenced_maps);
+        entities.extend(map.entities);
+        levels.insert(level_id, map.level);
+    }
+
+    let (player_starting_position, current_level_id) =
+        player_start.ok_or(TileMapError::NoPlayerStart)?;
+
+    Ok(TileMap {
+        levels,
+        current_level_id,
+        tilesize: tilesize.ok_or(TileMapError::NoLevels)?,
+        player_starting_position,
+        entities,
+        pending: HashMap::new(),
+        failed: HashMap::new(),
+        prefetcher: None,
+        tilesets: cache,
+    })
+}
+
+fn map_level_id(path: &Path) -> LevelId {
+    path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn entity_id(level_id: &str, object_id: &str) -> EntityId {
+    format!("{}#{}", level_id, object_id)
+}
+
+struct LoadedMap {
+    level: Level,
+    tilesize: i64,
+    entities: Vec<(EntityId, EntityInstance)>,
+    player_start: Option<Vec2>,
+    referenced_maps: Vec<PathBuf>,
+}
+
+fn load_map(
+    source: &AssetSource,
+    path: &Path,
+    level_id: &str,
+    cache: &TilesetCache,
+) -> Result<LoadedMap, TileMapError> {
+    let map = parse_xml(source, path)?;
+    let directory = path.parent().unwrap_or(Path::new("."));
+
+    if map.attribute("infinite") == Some("1") {
+        return Err(invalid(path, "infinite maps are not supported"));
+    }
+
+    let width: i64 = map.number("width", path)?;
+    let height: i64 = map.number("height", path)?;
+    let tile_width: i64 = map.number("tilewidth", path)?;
+    let tile_height: i64 = map.number("tileheight", path)?;
+    if tile_width <= 0 || tile_height <= 0 {
+        return Err(invalid(path, "tiles have to be at least 1px"));
+    }
+
+    let mut tilesets = Vec::new();
+    for element in map.children("tileset") {
+        tilesets.push(TiledTileset::load(source, element, path, directory, cache)?);
+    }
+    tilesets.sort_by_key(|tileset| tileset.first_gid);
+
+    let mut layers = MapLayers::default();
+    layers.collect(&map, LayerProperties::default());
+
+    let mut entities = Vec::new();
+    let mut player_start = None;
+    let mut referenced_maps = Vec::new();
+    let mut pressure_plates = HashMap::new();
+
+    // Entities, with the transitions and pressure plates they place on the grid
+    let mut transitions = HashMap::new();
+    for object in layers.objects {
+        let object_id = object.attribute("id").unwrap_or_default();
+        let iid = entity_id(level_id, object_id);
+        let properties = Properties::of(object);
+        let identifier = object
+            .attribute("class")
+            .or(object.attribute("type"))
+            .or(object.attribute("name"))
+            .unwrap_or_default();
+
+        let gid = object.attribute("gid").and_then(|gid| gid.parse::<u32>().ok());
+        let mut x: f32 = object.number("x", path)?;
+        let mut y: f32 = object.number("y", path)?;
+        if gid.is_some() {
+            // Tile objects are positioned by their bottom left corner
+            y -= object
+                .attribute("height")
+                .and_then(|h| h.parse::<f32>().ok())
+                .unwrap_or(tile_height as f32);
+        }
+        x = x.round();
+        y = y.round();
+        let cell = (
+            (x as i64).div_euclid(tile_width),
+            (y as i64).div_euclid(tile_height),
+        );
+
+        let invalid_field = |field: &str, reason: String| TileMapError::InvalidField {
+            level: level_id.to_string(),
+            entity_iid: iid.clone(),
+            field: field.to_string(),
+            reason,
+        };
+
+        match identifier {
+            "PlayerStart" => {
+                player_start.get_or_insert(Vec2::new(x, y));
+            }
+            "Entrance" => {
+                if let Some(reference) = properties.get("Entity_ref") {
+                    let destination = match reference.kind.as_str() {
+                        "object" => entity_id(level_id, &reference.value),
+                        _ => {
+                            let (map_file, object_id) =
+                                reference.value.split_once('#').ok_or_else(|| {
+                                    invalid_field(
+                                        "Entity_ref",
+                                        "expected an object or map.tmx#id".to_string(),
+                                    )
+                                })?;
+                            let map_path = directory.join(map_file);
+                            let destination = entity_id(&map_level_id(&map_path), object_id);
+                            referenced_maps.push(map_path);
+                            destination
+                        }
+                    };
+
+                    transitions.insert(
+                        cell,
+                        read_transition(
+                            destination,
+                            |field| properties.str(field),
+                            |field| properties.bool(field),
+                        ),
+                    );
+                }
+            }
+            "PressurePlate" => {
+                if let Some(flag) = properties.str("Flag") {
+                    pressure_plates.insert(cell, flag.to_string());
+                }
+            }
+            _ => {}
+        }
+
+        let direction = match (identifier, properties.str("Direction")) {
+            ("Destination", Some(direction)) => Some(
+                Direction::from_str(direction)
+                    .map_err(|reason| invalid_field("Direction", reason))?,
+            ),
+            _ => None,
+        };
+
+        let tile = gid.and_then(|gid| {
+            let (id, _, _) = decode_gid(gid);
+            let tileset = find_tileset(&tilesets, id)?;
+            Some(EntityTile {
+                tileset_id: tileset.first_gid as i64,
+                tileset_position: tileset.position(id - tileset.first_gid),
+            })
+        });
+
+        entities.push((
+            iid.clone(),
+            EntityInstance {
+                identifier: identifier.to_string(),
+                position: Vec2::new(x, y),
+                level_id: level_id.to_string(),
+                direction,
+                tile,
+            },
+        ));
+    }
+
+    // Gameplay data for every cell, from the Collision layer if there is one
+    let collision_gids = match layers.collision {
+        Some(layer) => layer_gids(layer, path)?,
+        None => Vec::new(),
+    };
+    let tiles = TileGrid::from_fn(width, height, |cell| {
+        let gid = collision_gids.get((cell.1 * width + cell.0) as usize).copied().unwrap_or(0);
+        let (id, _, _) = decode_gid(gid);
+        let terrain = if id == 0 {
+            Terrain::Ground
+        } else {
+            find_tileset(&tilesets, id)
+                .and_then(|tileset| tileset.classes.get(&(id - tileset.first_gid)))
+                .map_or(Terrain::Wall, |class| Terrain::from_identifier(class))
+        };
+
+        TileData {
+            position: IVec2::new(cell.0 * tile_width, cell.1 * tile_height),
+            traversable: terrain.is_walkable(),
+            terrain,
+            transition: transitions.remove(&cell),
+        }
+    });
+
+    // Drawn layers, Tiled lists them from bottom to top
+    let mut tile_layers = Vec::new();
+    for (layer, properties) in layers.tiles {
+        let gids = layer_gids(layer, path)?;
+        let layer_width: i64 = layer.number("width", path)?;
+        let layer_height: i64 = layer.number("height", path)?;
+        let identifier = layer.attribute("name").unwrap_or_default().to_string();
+        if layer_width <= 0 {
+            return Err(TileMapError::InvalidTile {
+                level: level_id.to_string(),
+                layer: identifier,
+                reason: format!("layer is {} tiles wide", layer_width),
+            });
+        }
+
+        // Layers can mix tilesets, so they are split into one layer per tileset
+        let mut split_layers: Vec<(TilesetId, TileGrid<Vec<LayerTile>>)> = Vec::new();
+        for (index, gid) in gids.into_iter().enumerate() {
+            let (id, flip_x, flip_y) = decode_gid(gid);
+            let Some(tileset) = find_tileset(&tilesets, id) else {
+                continue;
+            };
+            let local_id = id - tileset.first_gid;
+            let cell = (index as i64 % layer_width, index as i64 / layer_width);
+
+            let tileset_id = tileset.first_gid as i64;
+            let index = match split_layers.iter().position(|(id, _)| *id == tileset_id) {
+                Some(index) => index,
+                None => {
+                    split_layers.push((tileset_id, TileGrid::new(layer_width, layer_height)));
+                    split_layers.len() - 1
+                }
+            };
+
+            let Some(stack) = split_layers[index].1.get_mut(cell) else {
+                return Err(invalid(
+                    path,
+                    &format!("layer {} has more tiles than cells", identifier),
+                ));
+            };
+            stack.push(LayerTile {
+                tileset_position: tileset.position(local_id),
+                // Tiles larger than the grid are anchored to the bottom left of their cell
+                position: IVec2::new(
+                    cell.0 * tile_width,
+                    (cell.1 + 1) * tile_height - tileset.tile_height,
+                ),
+                animation: tileset.animation(local_id),
+                flip_x,
+                flip_y,
+                alpha: 1.0,
+            });
+        }
+
+        tile_layers.extend(split_layers.into_iter().map(|(tileset_id, tiles)| {
+            TileLayer::new(
+                identifier.clone(),
+                tileset_id,
+                properties.opacity,
+                properties.offset,
+                tiles,
+            )
+        }));
+    }
+
+    let mut level = Level {
+        identifier: level_id.to_string(),
+        tiles,
+        layers: tile_layers,
+        tilesets: tilesets
+            .into_iter()
+            .map(|tileset| (tileset.first_gid as i64, tileset.tileset))
+            .collect(),
+        pressure_plates,
+        indoors: Properties::of(&map).bool("indoors"),
+        world_position: IVec2::ZERO,
+        pixel_width: width * tile_width,
+        pixel_height: height * tile_height,
+        neighbours: Vec::new(),
+    };
+    level.close_doors();
+
+    Ok(LoadedMap {
+        level,
+        tilesize: tile_width,
+        entities,
+        player_start,
+        referenced_maps,
+    })
+}
+
+/// Splits a global tile id into the tile id and its flips. Diagonal flips, used by Tiled for
+/// rotated tiles, are not supported and ignored.
+fn decode_gid(gid: u32) -> (u32, bool, bool) {
+    let id =
+        gid & !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL);
+    (
+        id,
+        gid & FLIPPED_HORIZONTALLY != 0,
+        gid & FLIPPED_VERTICALLY != 0,
+    )
+}
+
+fn find_tileset(tilesets: &[TiledTileset], id: u32) -> Option<&TiledTileset> {
+    if id == 0 {
+        return None;
+    }
+    tilesets.iter().rev().find(|tileset| tileset.first_gid <= id)
+}
+
+#[derive(Clone, Copy)]
+struct LayerProperties {
+    opacity: f32,
+    offset: IVec2,
+}
+
+impl Default for LayerProperties {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            offset: IVec2::ZERO,
+        }
+    }
+}
+
+impl LayerProperties {
+    /// Properties of a layer inside a group, groups apply their opacity and offset to every layer
+    /// in them
+    fn nested(self, element: &Element) -> Self {
+        let opacity =
+            element.attribute("opacity").and_then(|o| o.parse::<f32>().ok()).unwrap_or(1.0);
+        let offset = |name| {
+            element.attribute(name).and_then(|o| o.parse::<f32>().ok()).unwrap_or(0.0).round()
+                as i64
+        };
+
+        Self {
+            opacity: self.opacity * opacity,
+            offset: self.offset + IVec2::new(offset("offsetx"), offset("offsety")),
+        }
+    }
+}
+
+/// Layers of a map, with the layers inside groups flattened
+#[derive(Default)]
+struct MapLayers<'a> {
+    /// Drawn tile layers from bottom to top
+    tiles: Vec<(&'a Element, LayerProperties)>,
+    collision: Option<&'a Element>,
+    objects: Vec<&'a Element>,
+}
+
+impl<'a> MapLayers<'a> {
+    fn collect(&mut self, element: &'a Element, properties: LayerProperties) {
+        for child in &element.children {
+            match child.name.as_str() {
+                "layer" if child.attribute("name") == Some("Collision") => {
+                    self.collision = Some(child)
+                }
+                "layer" => self.tiles.push((child, properties.nested(child))),
+                "objectgroup" => self.objects.extend(child.children("object")),
+                "group" => self.collect(child, properties.nested(child)),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn layer_gids(layer: &Element, path: &Path) -> Result<Vec<u32>, TileMapError> {
+    let data = layer.child("data").ok_or_else(|| invalid(path, "tile layer without data"))?;
+
+    match data.attribute("encoding") {
+        Some("csv") => data
+            .text
+            .split(',')
+            .map(str::trim)
+            .filter(|gid| !gid.is_empty())
+            .map(|gid| {
+                gid.parse::<u32>().map_err(|_| invalid(path, &format!("invalid tile {:?}", gid)))
+            })
+            .collect(),
+        None => Ok(data
+            .children("tile")
+            .map(|tile| tile.attribute("gid").and_then(|gid| gid.parse().ok()).unwrap_or(0))
+            .collect()),
+        Some(encoding) => Err(invalid(
+            path,
+            &format!(
+                "{} layer data is not supported, save the map with the CSV layer format",
+                encoding
+            ),
+        )),
+    }
+}
+
+struct TiledTileset {
+    first_gid: u32,
+    tile_width: i64,
+    tile_height: i64,
+    columns: i64,
+    spacing: i64,
+    margin: i64,
+    tileset: Arc<Tileset>,
+    /// Class of every tile that has one, used for terrain in the Collision layer
+    classes: HashMap<u32, String>,
+    /// Tile id and duration of each frame of every animated tile
+    animations: HashMap<u32, Vec<(u32, Duration)>>,
+}
+
+impl TiledTileset {
+    /// Loads a tileset that is either embedded in the map or referenced as a .tsx file
+    fn load(
+        source: &AssetSource,
+        element: &Element,
+        map_path: &Path,
+        map_directory: &Path,
+        cache: &TilesetCache,
+    ) -> Result<Self, TileMapError> {
+        let first_gid = element.number("firstgid", map_path)?;
+
+        let external;
+        let (tileset, path, directory) = match element.attribute("source") {
+            Some(tileset_path) => {
+                let path = map_directory.join(tileset_path);
+                external = (parse_xml(source, &path)?, path);
+                let directory = external.1.parent().unwrap_or(Path::new(".")).to_path_buf();
+                (&external.0, external.1.as_path(), directory)
+            }
+            None => (element, map_path, map_directory.to_path_buf()),
+        };
+
+        let image = tileset
+            .child("image")
+            .ok_or_else(|| invalid(path, "image collection tilesets are not supported"))?;
+        let tileset_image =
+            cache.load(&directory.join(image.attribute("source").unwrap_or_default()))?;
+
+        let mut classes = HashMap::new();
+        let mut animations = HashMap::new();
+        for tile in tileset.children("tile") {
+            let id: u32 = tile.number("id", path)?;
+
+            if let Some(class) = tile.attribute("class").or(tile.attribute("type")) {
+                classes.insert(id, class.to_string());
+            }
+
+            if let Some(animation) = tile.child("animation") {
+                let frames = animation
+                    .children("frame")
+                    .map(|frame| {
+                        let tile_id: u32 = frame.number("tileid", path)?;
+                        let duration = frame.number("duration", path)?;
+                        Ok((tile_id, Duration::from_millis(duration)))
+                    })
+                    .collect::<Result<Vec<_>, TileMapError>>()?;
+                if frames.is_empty() {
+                    return Err(TileMapError::InvalidAnimation {
+                        tileset: tileset.attribute("name").unwrap_or_default().to_string(),
+                        tile_id: id as i64,
+                        reason: "animation has no frames".to_string(),
+                    });
+                }
+                animations.insert(id, frames);
+            }
+        }
+
+        Ok(Self {
+            first_gid,
+            tile_width: tileset.number("tilewidth", path)?,
+            tile_height: tileset.number("tileheight", path)?,
+            columns: tileset.number("columns", path)?,
+            spacing: tileset.attribute("spacing").and_then(|s| s.parse().ok()).unwrap_or(0),
+            margin: tileset.attribute("margin").and_then(|m| m.parse().ok()).unwrap_or(0),
+            tileset: tileset_image,
+            classes,
+            animations,
+        })
+    }
+
+    /// Pixel position of a tile in the tileset image
+    fn position(&self, id: u32) -> IVec2 {
+        let columns = self.columns.max(1);
+        let column = id as i64 % columns;
+        let row = id as i64 / columns;
+        IVec2::new(
+            self.margin + column * (self.tile_width + self.spacing),
+            self.margin + row * (self.tile_height + self.spacing),
+        )
+    }
+
+    fn animation(&self, id: u32) -> Option<TileAnimation> {
+        let frames = self.animations.get(&id)?;
+        let frames = frames.iter().map(|(id, duration)| (self.position(*id), *duration));
+        Some(TileAnimation::with_durations(frames.collect()))
+    }
+}
+
+struct Property {
+    kind: String,
+    value: String,
+}
+
+/// Custom properties of a map or object
+struct Properties(HashMap<String, Property>);
+
+impl Properties {
+    fn of(element: &Element) -> Self {
+        let properties = element
+            .child("properties")
+            .into_iter()
+            .flat_map(|properties| properties.children("property"))
+            .filter_map(|property| {
+                let name = property.attribute("name")?.to_string();
+                let kind = property.attribute("type").unwrap_or("string").to_string();
+                // Multiline strings are stored as text instead of in the value attribute
+                let value = property
+                    .attribute("value")
+                    .map_or_else(|| property.text.clone(), str::to_string);
+                Some((name, Property { kind, value }))
+            })
+            .collect();
+        Self(properties)
+    }
+
+    /// A property that is set, Tiled stores unset object references as 0
+    fn get(&self, name: &str) -> Option<&Property> {
+        self.0.get(name).filter(|property| {
+            let unset_object = property.kind == "object" && property.value == "0";
+            !property.value.is_empty() && !unset_object
+        })
+    }
+
+    fn str(&self, name: &str) -> Option<&str> {
+        self.get(name).map(|property| property.value.as_str())
+    }
+
+    fn bool(&self, name: &str) -> bool {
+        self.str(name) == Some("true")
+    }
+}
+
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+
+    fn number<T: FromStr>(&self, name: &str, path: &Path) -> Result<T, TileMapError> {
+        self.attribute(name).and_then(|value| value.parse().ok()).ok_or_else(|| {
+            invalid(
+                path,
+                &format!(
+                    "<{}> has a missing or invalid {} attribute",
+                    self.name, name
+                ),
+            )
+        })
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+fn parse_xml(source: &AssetSource, path: &Path) -> Result<Element, TileMapError> {
+    let bytes = source.read(path).map_err(|error| TileMapError::Io {
+        path: source.display_path(path),
+        source: error,
+    })?;
+
+    let mut stack: Vec<Element> = Vec::new();
+    for event in EventReader::new(&bytes[..]) {
+        let event = event.map_err(|error| TileMapError::Xml {
+            path: source.display_path(path),
+            source: error,
+        })?;
+
+        match event {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => stack.push(Element {
+                name: name.local_name,
+                attributes: attributes
+                    .into_iter()
+                    .map(|attribute| (attribute.name.local_name, attribute.value))
+                    .collect(),
+                ..Default::default()
+            }),
+            XmlEvent::EndElement { .. } => {
+                let Some(element) = stack.pop() else {
+                    continue;
+                };
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Err(invalid(path, "the document has no root element"))
+}
+
+fn invalid(path: &Path, reason: &str) -> TileMapError {
+    TileMapError::InvalidTiled {
+        path: path.to_string_lossy().into_owned(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, SMALL_TILED};
+
+    #[test]
+    fn decode_gid_strips_flip_flags() {
+        assert_eq!(decode_gid(5), (5, false, false));
+        assert_eq!(decode_gid(5 | FLIPPED_HORIZONTALLY), (5, true, false));
+        assert_eq!(
+            decode_gid(5 | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY),
+            (5, false, true)
+        );
+    }
+
+    #[test]
+    fn map_is_loaded_with_terrain_from_tile_classes() {
+        let tilemap = test_fixtures::load(SMALL_TILED);
+        let level = tilemap.current_level();
+
+        assert_eq!(tilemap.tilesize(), 16);
+        assert_eq!(tilemap.player_starting_position, Vec2::new(16.0, 16.0));
+        assert_eq!(level.tiles.get((3, 0)).unwrap().terrain, Terrain::Wall);
+        assert_eq!(level.tiles.get((0, 2)).unwrap().terrain, Terrain::Water);
+        assert!(level.tiles.get((1, 1)).unwrap().traversable);
+    }
+
+    #[test]
+    fn map_with_tiles_of_zero_pixels_is_rejected() {
+        let error = test_fixtures::load_replaced(
+            SMALL_TILED,
+            SMALL_TILED,
+            r#"tilewidth="16" tileheight="16" infinite"#,
+            r#"tilewidth="0" tileheight="16" infinite"#,
+        )
+        .unwrap_err();
+
+        assert!(matches!(error, TileMapError::InvalidTiled { .. }));
+    }
+
+    #[test]
+    fn layer_without_width_is_rejected() {
+        let error = test_fixtures::load_replaced(
+            SMALL_TILED,
+            SMALL_TILED,
+            r#"name="Ground" width="4""#,
+            r#"name="Ground" width="0""#,
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            TileMapError::InvalidTile { ref layer, .. } if layer == "Ground"
+        ));
+    }
+
+    #[test]
+    fn animation_frames_keep_their_own_durations() {
+        let tilemap = test_fixtures::load(SMALL_TILED);
+        let ground = &tilemap.current_level().layers[0];
+        let mut animation = ground.tiles.get((3, 1)).unwrap()[0].animation.clone().unwrap();
+
+        assert_eq!(animation.frames(), [IVec2::new(16, 16), IVec2::new(0, 0)]);
+        animation.update(Duration::from_millis(100));
+        assert_eq!(animation.current_position(), IVec2::new(0, 0));
+        // The second frame lasts 400ms
+        animation.update(Duration::from_millis(300));
+        assert_eq!(animation.current_position(), IVec2::new(0, 0));
+        animation.update(Duration::from_millis(100));
+        assert_eq!(animation.current_position(), IVec2::new(16, 16));
+    }
+}
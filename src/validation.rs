@@ -0,0 +1,505 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt,
+    path::Path,
+};
+
+use ldtk2::Ldtk;
+use serde_json::json;
+
+use crate::{
+    assets::{AssetSource, ImageCache},
+    tile::{LoadOptions, TileMap, TileMapError, TilesetCache},
+    vec2::Vec2,
+};
+
+/// A problem in a level that the engine would either refuse to load or trip over at runtime
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub kind: IssueKind,
+    /// Identifier of the level in LDtk, if the issue belongs to one
+    pub level: Option<String>,
+    /// Tile coordinate in the level
+    pub tile: Option<(i64, i64)>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IssueKind {
+    LoadFailed,
+    MissingTilesetImage,
+    MissingDestination,
+    /// An Entrance without an Entity_ref, which the loader ignores
+    EntranceWithoutDestination,
+    MissingDirection,
+    UnreachablePlayerStart,
+    /// A level the player can't walk to from PlayerStart
+    UnreachableLevel,
+    /// An Entrance the player can't walk to in a level they can reach
+    UnreachableEntrance,
+    TileOutsideCollisionGrid,
+    AnimationOutOfRange,
+}
+
+impl IssueKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IssueKind::LoadFailed => "load_failed",
+            IssueKind::MissingTilesetImage => "missing_tileset_image",
+            IssueKind::MissingDestination => "missing_destination",
+            IssueKind::EntranceWithoutDestination => "entrance_without_destination",
+            IssueKind::MissingDirection => "missing_direction",
+            IssueKind::UnreachablePlayerStart => "unreachable_player_start",
+            IssueKind::UnreachableLevel => "unreachable_level",
+            IssueKind::UnreachableEntrance => "unreachable_entrance",
+            IssueKind::TileOutsideCollisionGrid => "tile_outside_collision_grid",
+            IssueKind::AnimationOutOfRange => "animation_out_of_range",
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.kind.name())?;
+        if let Some(level) = &self.level {
+            write!(f, " {}", level)?;
+        }
+        if let Some((x, y)) = self.tile {
+            write!(f, " ({}, {})", x, y)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Loads an LDtk project or Tiled map and validates it. Tileset images that can't be loaded are
+/// reported one by one and replaced with blank images, so the levels using them are still checked.
+/// Any other problem that keeps the project from loading is reported as a single issue.
+pub fn validate_file(source: &AssetSource, path: &str) -> Vec<Issue> {
+    let tilesets = TilesetCache::new(ImageCache::new(source.clone()));
+    let mut issues = check_tileset_images(&tilesets, path);
+
+    match TileMap::load_with_tilesets(tilesets, path, LoadOptions::default()) {
+        Ok(tilemap) => issues.extend(validate(&tilemap)),
+        Err(error) => issues.push(load_failed(error)),
+    }
+
+    sort(&mut issues);
+    issues
+}
+
+/// Loads every tileset image of an LDtk project into the cache, standing in a blank image of the
+/// tileset's size for each one that can't be loaded
+fn check_tileset_images(tilesets: &TilesetCache, path: &str) -> Vec<Issue> {
+    // Tiled tilesets are read from the maps, which report a missing image when they are loaded
+    if path.ends_with(".tmx") {
+        return Vec::new();
+    }
+
+    let source = tilesets.source();
+    // Projects that can't be read are reported when they are loaded
+    let Ok(json) = source.read_to_string(path) else {
+        return Vec::new();
+    };
+    let Ok(ldtk) = Ldtk::from_str(&json) else {
+        return Vec::new();
+    };
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    let mut issues = Vec::new();
+    for tileset in &ldtk.defs.tilesets {
+        let Some(rel_path) = &tileset.rel_path else {
+            continue;
+        };
+        let image_path = directory.join(rel_path);
+        if let Err(error) = tilesets.load(&image_path) {
+            issues.push(Issue {
+                kind: IssueKind::MissingTilesetImage,
+                level: None,
+                tile: None,
+                message: format!("Tileset {}: {}", tileset.identifier, error),
+            });
+            tilesets.insert_blank(
+                &image_path,
+                tileset.px_wid.max(0) as u32,
+                tileset.px_hei.max(0) as u32,
+            );
+        }
+    }
+    issues
+}
+
+pub fn validate(tilemap: &TileMap) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let tilesize = tilemap.tilesize();
+
+    for (_, level) in tilemap.levels() {
+        let issue = |kind, tile, message| Issue {
+            kind,
+            level: Some(level.identifier.clone()),
+            tile: Some(tile),
+            message,
+        };
+
+        for (tile, data) in level.tiles.iter() {
+            let Some(transition) = &data.transition else {
+                continue;
+            };
+
+            match tilemap.entities.get(&transition.destination) {
+                None => issues.push(issue(
+                    IssueKind::MissingDestination,
+                    tile,
+                    format!(
+                        "Entrance points to missing entity {}",
+                        transition.destination
+                    ),
+                )),
+                Some(destination) if destination.direction.is_none() => issues.push(issue(
+                    IssueKind::MissingDirection,
+                    tile,
+                    format!("Destination {} has no Direction", transition.destination),
+                )),
+                Some(_) => {}
+            }
+        }
+
+        for layer in &level.layers {
+            let tileset = level.tilesets.get(&layer.tileset_id);
+
+            for (cell, stack) in layer.tiles.iter().filter(|(_, stack)| !stack.is_empty()) {
+                if !level.tiles.contains(cell) {
+                    issues.push(issue(
+                        IssueKind::TileOutsideCollisionGrid,
+                        cell,
+                        format!(
+                            "Layer {} has a tile outside the collision grid",
+                            layer.identifier
+                        ),
+                    ));
+                }
+
+                let Some(tileset) = tileset else {
+                    continue;
+                };
+
+                let out_of_range =
+                    stack.iter().filter_map(|tile| tile.animation.as_ref()).any(|animation| {
+                        animation.frames().iter().any(|frame| {
+                            frame.x < 0
+                                || frame.y < 0
+                                || frame.x + tilesize > tileset.width as i64
+                                || frame.y + tilesize > tileset.height as i64
+                        })
+                    });
+                if out_of_range {
+                    issues.push(issue(
+                        IssueKind::AnimationOutOfRange,
+                        cell,
+                        format!(
+                            "Animation in layer {} uses a tile outside its tileset",
+                            layer.identifier
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    for (entity_id, entity) in &tilemap.entities {
+        if entity.identifier != "Entrance" {
+            continue;
+        }
+        let Some(level) = tilemap.get_level(&entity.level_id) else {
+            continue;
+        };
+        let tile = tile_at(entity.position, tilesize);
+        let has_destination = level.tiles.get(tile).is_some_and(|tile| tile.transition.is_some());
+        if !has_destination {
+            issues.push(Issue {
+                kind: IssueKind::EntranceWithoutDestination,
+                level: Some(level.identifier.clone()),
+                tile: Some(tile),
+                message: format!("Entrance {} has no Entity_ref", entity_id),
+            });
+        }
+    }
+
+    let start_tile = tile_at(tilemap.player_starting_position, tilesize);
+    let start_level = tilemap.current_level();
+    let walkable = start_level.tiles.get(start_tile).is_some_and(|tile| tile.traversable);
+    if !walkable {
+        issues.push(Issue {
+            kind: IssueKind::UnreachablePlayerStart,
+            level: Some(start_level.identifier.clone()),
+            tile: Some(start_tile),
+            message: "PlayerStart is not on a walkable tile".to_string(),
+        });
+    }
+
+    let reachable = reachable_tiles(tilemap, start_tile);
+    for (id, level) in tilemap.levels() {
+        if !reachable.iter().any(|(level_id, _)| level_id == id) {
+            issues.push(Issue {
+                kind: IssueKind::UnreachableLevel,
+                level: Some(level.identifier.clone()),
+                tile: None,
+                message: "Level can't be reached from PlayerStart".to_string(),
+            });
+            continue;
+        }
+
+        for (tile, data) in level.tiles.iter() {
+            if data.transition.is_some() && !reachable.contains(&(id.as_str(), tile)) {
+                issues.push(Issue {
+                    kind: IssueKind::UnreachableEntrance,
+                    level: Some(level.identifier.clone()),
+                    tile: Some(tile),
+                    message: "Entrance can't be reached from PlayerStart".to_string(),
+                });
+            }
+        }
+    }
+
+    sort(&mut issues);
+    issues
+}
+
+/// Tiles the player can walk to from PlayerStart, going through entrances and across the borders
+/// of outdoor levels. Locked entrances count as open, since the flag or item may be found later.
+fn reachable_tiles(tilemap: &TileMap, start_tile: (i64, i64)) -> HashSet<(&str, (i64, i64))> {
+    let tilesize = tilemap.tilesize();
+    let mut reachable = HashSet::new();
+    let mut queue = VecDeque::from([(tilemap.current_level_id(), start_tile)]);
+
+    while let Some((level_id, tile)) = queue.pop_front() {
+        if !reachable.insert((level_id, tile)) {
+            continue;
+        }
+        let Some(level) = tilemap.get_level(level_id) else {
+            continue;
+        };
+
+        if let Some(transition) = level.tiles.get(tile).and_then(|tile| tile.transition.as_ref()) {
+            if let Some(destination) = tilemap.entities.get(&transition.destination) {
+                let destination_tile = tile_at(destination.position, tilesize);
+                queue.push_back((destination.level_id.as_str(), destination_tile));
+            }
+        }
+
+        for direction in [(0, -1), (0, 1), (-1, 0), (1, 0)] {
+            let next = (tile.0 + direction.0, tile.1 + direction.1);
+            if let Some(next_tile) = level.tiles.get(next) {
+                if next_tile.traversable {
+                    queue.push_back((level_id, next));
+                }
+                continue;
+            }
+
+            // Walking off an outdoor level leads into the outdoor neighbour on the other side
+            if level.indoors {
+                continue;
+            }
+            for (neighbour_id, neighbour) in tilemap.levels() {
+                if neighbour.indoors || !level.neighbours.contains(neighbour_id) {
+                    continue;
+                }
+                let offset = neighbour.world_position - level.world_position;
+                let neighbour_tile = (next.0 - offset.x / tilesize, next.1 - offset.y / tilesize);
+                if neighbour.tiles.get(neighbour_tile).is_some_and(|tile| tile.traversable) {
+                    queue.push_back((neighbour_id, neighbour_tile));
+                }
+            }
+        }
+    }
+
+    reachable
+}
+
+fn tile_at(position: Vec2, tilesize: i64) -> (i64, i64) {
+    (
+        (position.x as i64).div_euclid(tilesize),
+        (position.y as i64).div_euclid(tilesize),
+    )
+}
+
+fn sort(issues: &mut [Issue]) {
+    issues.sort_by(|a, b| (&a.level, a.tile, a.kind).cmp(&(&b.level, b.tile, b.kind)));
+}
+
+pub fn to_json(issues: &[Issue]) -> String {
+    let issues = issues
+        .iter()
+        .map(|issue| {
+            json!({
+                "kind": issue.kind.name(),
+                "level": issue.level,
+                "tile": issue.tile.map(|(x, y)| [x, y]),
+                "message": issue.message,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::to_string_pretty(&json!({ "issues": issues })).unwrap_or_default()
+}
+
+fn load_failed(error: TileMapError) -> Issue {
+    Issue {
+        kind: IssueKind::LoadFailed,
+        level: None,
+        tile: None,
+        message: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::test_fixtures::{self, SMALL};
+
+    fn kinds(change: impl FnOnce(&mut Value)) -> Vec<IssueKind> {
+        let source = test_fixtures::modified(SMALL, change);
+        validate_file(&source, SMALL).into_iter().map(|issue| issue.kind).collect()
+    }
+
+    /// Layer of a level in the small fixture, where Start is level 0 and House level 1
+    fn layer<'a>(project: &'a mut Value, level: usize, identifier: &str) -> &'a mut Value {
+        let layers = project["levels"][level]["layerInstances"].as_array_mut().unwrap();
+        layers.iter_mut().find(|layer| layer["__identifier"] == identifier).unwrap()
+    }
+
+    fn entity<'a>(project: &'a mut Value, level: usize, iid: &str) -> &'a mut Value {
+        let entities = layer(project, level, "Entities")["entityInstances"].as_array_mut().unwrap();
+        entities.iter_mut().find(|entity| entity["iid"] == iid).unwrap()
+    }
+
+    /// Sets a cell of the 4 tiles wide Collision layer of Start
+    fn set_collision(project: &mut Value, cell: (usize, usize), value: i64) {
+        layer(project, 0, "Collision")["intGridCsv"][cell.1 * 4 + cell.0] = value.into();
+    }
+
+    #[test]
+    fn fixture_has_no_issues() {
+        assert_eq!(validate_file(&test_fixtures::source(), SMALL), []);
+    }
+
+    #[test]
+    fn project_that_fails_to_load_is_reported() {
+        let issues = kinds(|project| project["levels"] = Value::Array(Vec::new()));
+        assert_eq!(issues, [IssueKind::LoadFailed]);
+    }
+
+    #[test]
+    fn missing_tileset_image_is_reported_and_the_levels_are_still_checked() {
+        let issues = kinds(|project| {
+            project["defs"]["tilesets"][0]["relPath"] = "missing.png".into();
+            entity(project, 1, "house_arrival")["fieldInstances"][0]["__value"] = Value::Null;
+        });
+        assert_eq!(
+            issues,
+            [IssueKind::MissingTilesetImage, IssueKind::MissingDirection]
+        );
+    }
+
+    #[test]
+    fn entrance_to_a_missing_entity_is_reported() {
+        let issues = kinds(|project| {
+            let entrance = &mut layer(project, 1, "Entities")["entityInstances"][1];
+            entrance["fieldInstances"][0]["__value"]["entityIid"] = "nowhere".into();
+        });
+        assert_eq!(issues, [IssueKind::MissingDestination]);
+    }
+
+    #[test]
+    fn entrance_without_entity_ref_is_reported() {
+        let issues = kinds(|project| {
+            let entrance = &mut layer(project, 1, "Entities")["entityInstances"][1];
+            entrance["fieldInstances"][0]["__value"] = Value::Null;
+        });
+        assert_eq!(issues, [IssueKind::EntranceWithoutDestination]);
+    }
+
+    #[test]
+    fn destination_without_direction_is_reported() {
+        let issues = kinds(|project| {
+            entity(project, 1, "house_arrival")["fieldInstances"][0]["__value"] = Value::Null;
+        });
+        assert_eq!(issues, [IssueKind::MissingDirection]);
+    }
+
+    #[test]
+    fn player_start_on_a_wall_is_reported() {
+        let issues = kinds(|project| set_collision(project, (0, 1), 1));
+        assert_eq!(issues, [IssueKind::UnreachablePlayerStart]);
+    }
+
+    #[test]
+    fn levels_and_entrances_behind_walls_are_reported() {
+        let issues = kinds(|project| {
+            // Box in PlayerStart at (0, 1)
+            set_collision(project, (0, 0), 1);
+            set_collision(project, (0, 2), 1);
+            set_collision(project, (1, 1), 1);
+        });
+        assert_eq!(
+            issues,
+            [
+                IssueKind::UnreachableLevel,
+                IssueKind::UnreachableLevel,
+                IssueKind::UnreachableEntrance,
+            ]
+        );
+    }
+
+    #[test]
+    fn tile_outside_the_collision_grid_is_reported() {
+        let issues = kinds(|project| {
+            let grass = layer(project, 0, "Grass");
+            grass["__cWid"] = 5.into();
+            let tiles = grass["autoLayerTiles"].as_array_mut().unwrap();
+            let mut outside = tiles[0].clone();
+            outside["px"] = serde_json::json!([64, 0]);
+            tiles.push(outside);
+        });
+        assert_eq!(issues, [IssueKind::TileOutsideCollisionGrid]);
+    }
+
+    #[test]
+    fn animation_frame_outside_its_tileset_is_reported() {
+        let issues = kinds(|project| {
+            project["defs"]["tilesets"][0]["customData"][0]["data"] =
+                "animationTiles:3,9\nframeTime:100".into();
+        });
+        // Tile 3 is the door in Start and one of the Grass tiles
+        assert_eq!(
+            issues,
+            [
+                IssueKind::AnimationOutOfRange,
+                IssueKind::AnimationOutOfRange
+            ]
+        );
+    }
+
+    #[test]
+    fn issues_are_written_as_json() {
+        let issues = [Issue {
+            kind: IssueKind::UnreachableEntrance,
+            level: Some("Start".to_string()),
+            tile: Some((3, 3)),
+            message: "Entrance can't be reached from PlayerStart".to_string(),
+        }];
+
+        let json: Value = serde_json::from_str(&to_json(&issues)).unwrap();
+
+        assert_eq!(
+            json,
+            json!({ "issues": [{
+                "kind": "unreachable_entrance",
+                "level": "Start",
+                "tile": [3, 3],
+                "message": "Entrance can't be reached from PlayerStart",
+            }] })
+        );
+    }
+}
@@ -0,0 +1,52 @@
+//! Runs `pixels-engine-check` on the fixtures
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command, Output},
+};
+
+use serde_json::{json, Value};
+
+fn fixture(file: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(file)
+}
+
+fn check(path: &Path) -> (Output, Value) {
+    let output = Command::new(env!("CARGO_BIN_EXE_pixels-engine-check"))
+        .arg("--json")
+        .arg(path)
+        .output()
+        .unwrap();
+    let json = serde_json::from_slice(&output.stdout).unwrap();
+    (output, json)
+}
+
+#[test]
+fn valid_project_passes() {
+    let (output, json) = check(&fixture("small.ldtk"));
+
+    assert!(output.status.success());
+    assert_eq!(json, json!({ "issues": [] }));
+}
+
+#[test]
+fn issues_are_printed_as_json_and_fail_the_check() {
+    // A copy of the fixture whose tileset image doesn't exist
+    let directory = env::temp_dir().join(format!("pixels-engine-check-{}", process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let project = fs::read_to_string(fixture("small.ldtk")).unwrap();
+    let broken = directory.join("small.ldtk");
+    fs::write(
+        &broken,
+        project.replace(r#""relPath": "tiles.png""#, r#""relPath": "missing.png""#),
+    )
+    .unwrap();
+
+    let (output, json) = check(&broken);
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(json["issues"][0]["kind"], "missing_tileset_image");
+    assert_eq!(json["issues"].as_array().unwrap().len(), 1);
+}
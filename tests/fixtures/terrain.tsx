@@ -0,0 +1,8 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.10.2" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+ <image source="tiles.png" width="32" height="32"/>
+ <tile id="0" class="Ice"/>
+ <tile id="1" class="Conveyor_Down"/>
+ <tile id="2" class="Spinner_Clockwise"/>
+ <tile id="3" class="Wall"/>
+</tileset>